@@ -0,0 +1,64 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - typed syntax tree built by the parser
+ */
+use std::ops::Range;
+
+pub use sal_isa::{Prefix, Registry, StackAction};
+
+/// A value together with the byte range of the source line it was read from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Range<usize>,
+}
+
+/// One line of SAL that has code on it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Statement {
+    /// `THE START:`
+    Label(Spanned<String>),
+    /// `PLEASE, JUMP TO, THE START.`
+    Instruction(Instruction),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Instruction {
+    pub prefix: Prefix,
+    pub operation: Operation,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Operation {
+    Increment { registry: Registry, negatively: bool, using_other_registry: bool },
+    To { registry: Registry, action: StackAction },
+    Access { registry: Registry, outputting: bool, as_character: bool },
+    Repeat { registry: Registry, ending: bool, second_loop: bool },
+    BranchIfGreater { registry: Registry, label: Spanned<String> },
+    BranchIfZero { registry: Registry, label: Spanned<String> },
+    JumpIfEqual { label: Spanned<String> },
+    Jump { label: Spanned<String> },
+}
+
+impl Operation {
+    /// The label this operation jumps to, if it jumps.
+    pub fn label(&self) -> Option<&Spanned<String>> {
+        match self {
+            Operation::BranchIfGreater { label, .. }
+            | Operation::BranchIfZero { label, .. }
+            | Operation::JumpIfEqual { label }
+            | Operation::Jump { label } => Some(label),
+            _ => None,
+        }
+    }
+
+    pub fn label_mut(&mut self) -> Option<&mut Spanned<String>> {
+        match self {
+            Operation::BranchIfGreater { label, .. }
+            | Operation::BranchIfZero { label, .. }
+            | Operation::JumpIfEqual { label }
+            | Operation::Jump { label } => Some(label),
+            _ => None,
+        }
+    }
+}
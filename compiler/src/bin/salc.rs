@@ -0,0 +1,9 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - command line front end
+ */
+use the_socially_acceptable_language_compiler::compile;
+
+fn main() {
+    std::process::exit(compile(std::env::args().skip(1).collect()));
+}
@@ -0,0 +1,9 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - command line front end of the disassembler
+ */
+use the_socially_acceptable_language_compiler::disassemble_cli;
+
+fn main() {
+    std::process::exit(disassemble_cli(std::env::args().skip(1).collect()));
+}
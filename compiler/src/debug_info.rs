@@ -0,0 +1,19 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - the source line of every instruction, written next to the executable
+ */
+
+/// Extension of the debug info `salc --debug-info` writes next to an executable.
+pub const DEBUG_INFO_EXTENSION: &str = "saldbg";
+
+/// ## Debug info
+/// The 1-based source line every instruction of an executable was compiled from,
+/// as text, one line number per line, in the order of the instructions.
+pub fn write_debug_info(lines: &[usize]) -> String {
+    lines.iter().map(|_line| format!("{}\n", _line)).collect()
+}
+
+/// The source lines written by `write_debug_info`. `None` if `text` isn't debug info.
+pub fn read_debug_info(text: &str) -> Option<Vec<usize>> {
+    text.lines().map(|_line| _line.trim().parse().ok()).collect()
+}
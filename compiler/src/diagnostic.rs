@@ -0,0 +1,184 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - diagnostics reported for lines that fail to compile
+ */
+use std::fmt;
+use std::ops::Range;
+
+/// What part of an expression the compiler could not make sense of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    UnknownPrefix,
+    UnknownOperation,
+    UnknownRegistry,
+    UnknownArgument,
+    UnknownLabel,
+    LabelOutOfRange,
+    /// A polite jump to the instruction right after it, which it can't land on.
+    LabelTooClose,
+    LabelWrongDirection,
+    /// A label defined again, `Diagnostic::previous` points at the definition before.
+    DuplicateLabel,
+    /// The line ended, or a phrase was left empty, where something was expected.
+    Missing(&'static str),
+    /// A phrase after the end of an otherwise complete instruction.
+    UnexpectedPhrase,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownPrefix => write!(f, "unknown prefix"),
+            ErrorKind::UnknownOperation => write!(f, "unknown operation"),
+            ErrorKind::UnknownRegistry => write!(f, "unknown registry"),
+            ErrorKind::UnknownArgument => write!(f, "unknown argument"),
+            ErrorKind::UnknownLabel => write!(f, "unknown label"),
+            ErrorKind::LabelOutOfRange => write!(f, "label is out of range"),
+            ErrorKind::LabelTooClose => write!(f, "label is right after the jump, too close for a polite jump to land on"),
+            ErrorKind::LabelWrongDirection => write!(f, "label is in the wrong direction for this prefix"),
+            ErrorKind::DuplicateLabel => write!(f, "label is already defined"),
+            ErrorKind::Missing(_expected) => write!(f, "expected {}", _expected),
+            ErrorKind::UnexpectedPhrase => write!(f, "unexpected phrase"),
+        }
+    }
+}
+
+/// ## Diagnostic
+/// A single compile error, pointing at the offending phrase of a source line.
+///
+/// `line` is 1-based, `span` is a byte range into that line. `previous` is
+/// another line and span the error is about, such as where a label was defined before.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub span: Range<usize>,
+    pub phrase: String,
+    pub kind: ErrorKind,
+    pub hint: Option<String>,
+    pub previous: Option<Box<(usize, Range<usize>)>>,
+}
+
+impl Diagnostic {
+    pub fn new(kind: ErrorKind, line: usize, span: Range<usize>, phrase: &str) -> Diagnostic {
+        Diagnostic {
+            line,
+            span,
+            phrase: phrase.to_string(),
+            kind,
+            hint: None,
+            previous: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: Option<String>) -> Diagnostic {
+        self.hint = hint;
+        self
+    }
+
+    pub fn with_previous(mut self, line: usize, span: Range<usize>) -> Diagnostic {
+        self.previous = Some(Box::new((line, span)));
+        self
+    }
+
+    /// Render the diagnostic together with the source line it belongs to,
+    /// with carets under the offending phrase.
+    pub fn render(&self, source_line: &str) -> String {
+        let gutter = " ".repeat(self.line.to_string().len());
+        let start = source_line[..self.span.start.min(source_line.len())].chars().count();
+        let width = self.phrase.chars().count().max(1);
+
+        let mut rendered = format!("error: {}\n", self);
+        rendered += &format!("{}--> {}:{}\n", gutter, self.line, self.span.start + 1);
+        rendered += &format!("{} |\n", gutter);
+        rendered += &format!("{} | {}\n", self.line, source_line);
+        rendered += &format!("{} | {}{}\n", gutter, " ".repeat(start), "^".repeat(width));
+        if let Some((_line, _span)) = self.previous.as_deref() {
+            rendered += &format!("{} = note: defined before on line {}, column {}\n", gutter, _line, _span.start + 1);
+        }
+        if let Some(hint) = &self.hint {
+            rendered += &format!("{} = help: did you mean `{}`?\n", gutter, hint);
+        }
+        rendered
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.phrase.trim().is_empty() {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "{} `{}`", self.kind, self.phrase)
+        }
+    }
+}
+
+/// Find the phrase in `candidates` closest to `phrase`, if any is close enough
+/// to plausibly be a typo of it.
+pub fn nearest<'a>(phrase: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let phrase = phrase.trim().to_uppercase();
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(&phrase, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance <= candidate.chars().count() / 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Every diagnostic found while compiling a program, in source order.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.0.iter()
+    }
+
+    /// Render every diagnostic against the source it was found in, as given to the compiler.
+    pub fn render(&self, source: &str) -> String {
+        let source = crate::source::normalize(source);
+        let lines = crate::source::lines(&source);
+        self.iter()
+            .map(|_diagnostic| _diagnostic.render(lines.get(_diagnostic.line - 1).unwrap_or(&"")))
+            .collect()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for _diagnostic in self.iter() {
+            write!(f, "{}:{}: error: {}", _diagnostic.line, _diagnostic.span.start + 1, _diagnostic)?;
+            match _diagnostic.previous.as_deref() {
+                Some((_line, _span)) => writeln!(f, ", defined before at {}:{}", _line, _span.start + 1)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
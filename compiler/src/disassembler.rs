@@ -0,0 +1,141 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - turns executables back into SAL source
+ */
+use std::collections::BTreeSet;
+
+use crate::ast::{Instruction, Operation, Registry, Spanned};
+use crate::lexer::{self, Keyword, OperationName};
+
+/// Disassemble an executable into SAL source, one instruction per line.
+///
+/// Every jump target gets a `THE L<address>:` label, and every instruction
+/// is followed by its address and bits as a comment. The output compiles
+/// back to the same executable, as long as no jump leaves the program.
+pub fn disassemble(bytes: &[u8]) -> String {
+    let decoded: Vec<(Instruction, Option<isize>)> = bytes
+        .iter()
+        .enumerate()
+        .map(|(_address, _byte)| decode(*_byte, _address))
+        .collect();
+    let targets: BTreeSet<isize> = decoded.iter().filter_map(|(_, _target)| *_target).collect();
+    let width = bytes.len().saturating_sub(1).to_string().len().max(4);
+
+    let mut source = String::new();
+    for (_address, ((_instruction, _target), _byte)) in decoded.iter().zip(bytes).enumerate() {
+        if targets.contains(&(_address as isize)) {
+            source += &format!("{}:\n", label_name(_address as isize));
+        }
+        source += &format!("{} {:0width$} {:08b}", sentence(_instruction), _address, _byte, width = width);
+        match _target {
+            Some(_target) if *_target < 0 || *_target > bytes.len() as isize => {
+                source += " jumps outside the program";
+            }
+            _ => (),
+        }
+        source += "\n";
+    }
+    if targets.contains(&(bytes.len() as isize)) {
+        source += &format!("{}:\n", label_name(bytes.len() as isize));
+    }
+    source
+}
+
+/// Decode one byte with `sal_isa::decode`, along with the address it jumps to if it jumps.
+pub fn decode(byte: u8, address: usize) -> (Instruction, Option<isize>) {
+    let decoded = sal_isa::decode(byte);
+    let prefix = decoded.prefix;
+
+    // Where the emulator jumps to, through the same `sal_isa::target`: polite jumps land
+    // `distance + 2` ahead, demanding ones `distance` behind, branches included.
+    let target = |distance: u8| match sal_isa::target(prefix, address, sal_isa::offset(distance)) {
+        Some(_target) => _target as isize,
+        None => address as isize - distance as isize,
+    };
+    let label = |target: isize| Spanned { value: label_name(target), span: 0..0 };
+
+    let (operation, target) = match decoded.operation {
+        sal_isa::Operation::Increment { registry, negatively, using_other_registry } => {
+            (Operation::Increment { registry, negatively, using_other_registry }, None)
+        }
+        sal_isa::Operation::To { registry, action } => (Operation::To { registry, action }, None),
+        sal_isa::Operation::Access { registry, outputting, as_character } => {
+            (Operation::Access { registry, outputting, as_character }, None)
+        }
+        sal_isa::Operation::Repeat { registry, ending, second_loop } => {
+            (Operation::Repeat { registry, ending, second_loop }, None)
+        }
+        sal_isa::Operation::BranchIfGreater { registry, distance } => {
+            let target = target(distance);
+            (Operation::BranchIfGreater { registry, label: label(target) }, Some(target))
+        }
+        sal_isa::Operation::BranchIfZero { registry, distance } => {
+            let target = target(distance);
+            (Operation::BranchIfZero { registry, label: label(target) }, Some(target))
+        }
+        sal_isa::Operation::JumpIfEqual { distance } => {
+            let target = target(distance);
+            (Operation::JumpIfEqual { label: label(target) }, Some(target))
+        }
+        sal_isa::Operation::Jump { distance } => {
+            let target = target(distance);
+            (Operation::Jump { label: label(target) }, Some(target))
+        }
+    };
+    (Instruction { prefix, operation }, target)
+}
+
+/// Write an instruction as the canonical SAL sentence for it.
+pub fn sentence(instruction: &Instruction) -> String {
+    let pick = |choice: bool, options: [Keyword; 2]| lexer::phrase(options[choice as usize]);
+    let registry = |registry: Registry| lexer::phrase(Keyword::Registry(registry));
+
+    let mut phrases: Vec<String> = vec![lexer::phrase(Keyword::Prefix(instruction.prefix)).to_string()];
+    match &instruction.operation {
+        Operation::Increment { registry: _registry, negatively, using_other_registry } => {
+            phrases.push(lexer::phrase(Keyword::Operation(OperationName::Increment)).to_string());
+            phrases.push(registry(*_registry).to_string());
+            phrases.push(pick(*negatively, [Keyword::Positively, Keyword::Negatively]).to_string());
+            phrases.push(pick(*using_other_registry, [Keyword::UsingOne, Keyword::UsingTheOtherRegistry]).to_string());
+        }
+        Operation::To { registry: _registry, action } => {
+            phrases.push(lexer::phrase(Keyword::Operation(OperationName::To)).to_string());
+            phrases.push(registry(*_registry).to_string());
+            phrases.push(lexer::phrase(Keyword::StackAction(*action)).to_string());
+        }
+        Operation::Access { registry: _registry, outputting, as_character } => {
+            phrases.push(lexer::phrase(Keyword::Operation(OperationName::Access)).to_string());
+            phrases.push(registry(*_registry).to_string());
+            phrases.push(pick(*outputting, [Keyword::InputtingAValue, Keyword::OutputtingAValue]).to_string());
+            phrases.push(pick(*as_character, [Keyword::AsAnInteger, Keyword::AsACharacter]).to_string());
+        }
+        Operation::Repeat { registry: _registry, ending, second_loop } => {
+            phrases.push(lexer::phrase(Keyword::Operation(OperationName::Repeat)).to_string());
+            phrases.push(registry(*_registry).to_string());
+            phrases.push(pick(*ending, [Keyword::StartingHere, Keyword::EndingHere]).to_string());
+            phrases.push(pick(*second_loop, [Keyword::FirstLoop, Keyword::SecondLoop]).to_string());
+        }
+        Operation::BranchIfGreater { registry: _registry, label } | Operation::BranchIfZero { registry: _registry, label } => {
+            let name = match instruction.operation {
+                Operation::BranchIfGreater { .. } => OperationName::BranchIfGreater,
+                _ => OperationName::BranchIfZero,
+            };
+            phrases.push(lexer::phrase(Keyword::Operation(name)).to_string());
+            phrases.push(format!("{} {}", lexer::phrase(Keyword::Comparing), registry(*_registry)));
+            phrases.push(format!("{} {}", lexer::phrase(Keyword::AndJumpingTo), label.value));
+        }
+        Operation::JumpIfEqual { label } => {
+            phrases.push(lexer::phrase(Keyword::Operation(OperationName::JumpIfEqual)).to_string());
+            phrases.push(label.value.clone());
+        }
+        Operation::Jump { label } => {
+            phrases.push(lexer::phrase(Keyword::Operation(OperationName::Jump)).to_string());
+            phrases.push(label.value.clone());
+        }
+    }
+    format!("{}.", phrases.join(", "))
+}
+
+fn label_name(target: isize) -> String {
+    format!("THE L{}", target)
+}
@@ -0,0 +1,196 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - splits a line into keyword phrases, free words and punctuation
+ */
+use std::ops::Range;
+
+use crate::ast::{Prefix, Registry, StackAction};
+
+/// The operation phrases, before the parser knows what follows them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OperationName {
+    Increment,
+    To,
+    Access,
+    Repeat,
+    BranchIfGreater,
+    BranchIfZero,
+    JumpIfEqual,
+    Jump,
+}
+
+/// Every multi-word phrase SAL gives a meaning to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Keyword {
+    Prefix(Prefix),
+    Operation(OperationName),
+    Registry(Registry),
+    Comparing,
+    StackAction(StackAction),
+    Positively,
+    Negatively,
+    InputtingAValue,
+    OutputtingAValue,
+    StartingHere,
+    EndingHere,
+    UsingOne,
+    UsingTheOtherRegistry,
+    AsAnInteger,
+    AsACharacter,
+    FirstLoop,
+    SecondLoop,
+    AndJumpingTo,
+}
+
+pub const KEYWORDS: [(&str, Keyword); 32] = [
+    ("I'M ORDERING YOU", Keyword::Prefix(Prefix::Ordering)),
+    ("NOW", Keyword::Prefix(Prefix::Now)),
+    ("PLEASE", Keyword::Prefix(Prefix::Please)),
+    ("I'M BEGGING YOU", Keyword::Prefix(Prefix::Begging)),
+    ("INCREMENT", Keyword::Operation(OperationName::Increment)),
+    ("TO", Keyword::Operation(OperationName::To)),
+    ("ACCESS", Keyword::Operation(OperationName::Access)),
+    ("REPEAT THESE INSTRUCTIONS AN AMOUNT OF TIMES EQUAL TO", Keyword::Operation(OperationName::Repeat)),
+    ("IF THE SPECIFIED REGISTRY IS GREATER THAN THE UNSPECIFIED REGISTRY THEN JUMP TO THE SPECIFIED LABEL", Keyword::Operation(OperationName::BranchIfGreater)),
+    ("IF THE SPECIFIED REGISTRY IS EQUAL TO ZERO JUMP TO THE SPECIFIED LABEL", Keyword::Operation(OperationName::BranchIfZero)),
+    ("IF THE REGISTRIES ARE EQUAL THEN JUMP TO", Keyword::Operation(OperationName::JumpIfEqual)),
+    ("JUMP TO", Keyword::Operation(OperationName::Jump)),
+    ("THE FIRST REGISTRY", Keyword::Registry(Registry::First)),
+    ("THE SECOND REGISTRY", Keyword::Registry(Registry::Second)),
+    ("COMPARING", Keyword::Comparing),
+    ("DOUBLE THE VALUE", Keyword::StackAction(StackAction::Double)),
+    ("HALVE THE VALUE", Keyword::StackAction(StackAction::Halve)),
+    ("PUSH THE VALUE", Keyword::StackAction(StackAction::Push)),
+    ("POP THE VALUE", Keyword::StackAction(StackAction::Pop)),
+    ("POSITIVELY", Keyword::Positively),
+    ("NEGATIVELY", Keyword::Negatively),
+    ("INPUTTING A VALUE", Keyword::InputtingAValue),
+    ("OUTPUTTING A VALUE", Keyword::OutputtingAValue),
+    ("STARTING HERE", Keyword::StartingHere),
+    ("ENDING HERE", Keyword::EndingHere),
+    ("USING ONE", Keyword::UsingOne),
+    ("USING THE OTHER REGISTRY", Keyword::UsingTheOtherRegistry),
+    ("AS AN INTEGER", Keyword::AsAnInteger),
+    ("AS A CHARACTER", Keyword::AsACharacter),
+    ("AND THIS IS THE FIRST LOOP", Keyword::FirstLoop),
+    ("AND THIS IS THE SECOND LOOP", Keyword::SecondLoop),
+    ("AND JUMPING TO", Keyword::AndJumpingTo),
+];
+
+/// The phrase a keyword is written as.
+pub fn phrase(keyword: Keyword) -> &'static str {
+    KEYWORDS
+        .iter()
+        .find(|(_, _keyword)| *_keyword == keyword)
+        .map(|(_phrase, _)| *_phrase)
+        .unwrap_or("")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenKind {
+    Keyword(Keyword),
+    /// Words that are not part of any keyword, such as label names.
+    Words,
+    Comma,
+    Period,
+    Colon,
+    /// Everything after the period or colon that ends the statement.
+    Comment,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+/// Split one line into tokens. Never fails, anything unknown becomes `TokenKind::Words`.
+///
+/// A statement ends at its first period (or colon, for labels), so any
+/// punctuation in the comment after it is left alone.
+pub fn lex(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut segment_start = 0;
+    for (_index, _char) in line.char_indices() {
+        let kind = match _char {
+            ',' => TokenKind::Comma,
+            '.' => TokenKind::Period,
+            ':' => TokenKind::Colon,
+            _ => continue,
+        };
+        lex_words(line, segment_start.._index, &mut tokens);
+        tokens.push(Token { kind, span: _index.._index + 1 });
+        segment_start = _index + 1;
+
+        if kind != TokenKind::Comma {
+            let comment = line[segment_start..].trim();
+            if !comment.is_empty() {
+                let start = segment_start + line[segment_start..].find(comment).unwrap_or(0);
+                tokens.push(Token { kind: TokenKind::Comment, span: start..start + comment.len() });
+            }
+            return tokens;
+        }
+    }
+    lex_words(line, segment_start..line.len(), &mut tokens);
+    tokens
+}
+
+// Turn the words between two punctuation marks into keywords, always taking the longest phrase that matches.
+fn lex_words(line: &str, range: Range<usize>, tokens: &mut Vec<Token>) {
+    let mut words: Vec<Range<usize>> = Vec::new();
+    let mut word_start: Option<usize> = None;
+    for (_index, _char) in line[range.clone()].char_indices() {
+        let _index = range.start + _index;
+        match (_char.is_whitespace(), word_start) {
+            (true, Some(_start)) => {
+                words.push(_start.._index);
+                word_start = None;
+            }
+            (false, None) => word_start = Some(_index),
+            _ => (),
+        }
+    }
+    if let Some(_start) = word_start {
+        words.push(_start..range.end);
+    }
+
+    let mut free_words: Option<Range<usize>> = None;
+    let mut i = 0;
+    while i < words.len() {
+        let longest = KEYWORDS
+            .iter()
+            .filter_map(|(_phrase, _keyword)| {
+                let length = _phrase.split(' ').count();
+                let matches = i + length <= words.len()
+                    && _phrase
+                        .split(' ')
+                        .zip(&words[i..i + length])
+                        .all(|(_expected, _word)| _expected == &line[_word.clone()]);
+                if matches { Some((length, *_keyword)) } else { None }
+            })
+            .max_by_key(|(_length, _)| *_length);
+
+        match longest {
+            Some((_length, _keyword)) => {
+                if let Some(_span) = free_words.take() {
+                    tokens.push(Token { kind: TokenKind::Words, span: _span });
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Keyword(_keyword),
+                    span: words[i].start..words[i + _length - 1].end,
+                });
+                i += _length;
+            }
+            None => {
+                free_words = match free_words {
+                    Some(_span) => Some(_span.start..words[i].end),
+                    None => Some(words[i].clone()),
+                };
+                i += 1;
+            }
+        }
+    }
+    if let Some(_span) = free_words {
+        tokens.push(Token { kind: TokenKind::Words, span: _span });
+    }
+}
@@ -3,81 +3,378 @@
  * - file and error handling
  */
 
-mod compiler;
+pub mod ast;
+pub mod compiler;
+pub mod debug_info;
+pub mod diagnostic;
+pub mod disassembler;
+pub mod lexer;
+pub mod parser;
+pub mod relaxation;
+pub mod source;
+#[cfg(test)]
 mod tests;
 
 use std::fs;
 use std::io::prelude::*;
-use std::collections::HashMap;
-
-const OUT_FILE_PATH: &str = "./output.salexe";
-
-pub fn compile(arguments: Vec<String>) {
-    match fs::read_to_string(arguments[0].clone()) {
-        Ok(_contents) => {
-            println!("Compiling file: {:?}", arguments[0]);
-
-            let lines: Vec<String> = _contents
-                .split("\r\n")
-                .map(|_line| _line.to_string())
-                .collect();
-
-            
-            let mut current_index: usize = 0;
-            let mut labels: HashMap<String, usize> = HashMap::new();
-            while current_index < lines.len() {
-                if line_has_code(&lines[current_index]) {
-                    match compiler::find_labels(&lines[current_index]) {
-                        Some(_index) => labels.insert(lines[current_index][.._index].to_string(), current_index),
-                        None => None
-                    };
-                }
-                current_index += 1;
-            }
+use std::path::Path;
 
-            current_index = 0;
-            let mut executable: Vec<u8> = Vec::new();
-            while current_index < lines.len() {
-                if line_has_code(&lines[current_index]) {
-                    match compiler::run(lines[current_index].as_str(), &labels, &current_index) {
-                        Ok(_instruction) => {
-                            println!("{:?}:{:08b}", current_index+1, _instruction);
-                            executable.push(_instruction)},
-                        Err(_) => {
-                            println!("{:?}:-", current_index+1);
-                        },
-                    }
-                }
-                current_index += 1;
+use ast::Statement;
+use diagnostic::Diagnostics;
+
+// Exit statuses returned by `compile`.
+pub const SUCCESS: i32 = 0;
+pub const COMPILE_ERROR: i32 = 1;
+pub const IO_ERROR: i32 = 2;
+pub const USAGE_ERROR: i32 = 64;
+
+// Standard input or output, in place of a path.
+const STDIO: &str = "-";
+const EXTENSION: &str = "salexe";
+
+const USAGE: &str = "Usage: salc [OPTIONS] <INPUT>...
+
+Compiles SAL source files into executables for the SAL emulator.
+Use - as an input to read from stdin.
+
+Options:
+  -o <FILE>      Write the executable to FILE, or to stdout if FILE is -.
+                 Only allowed with a single input. By default each input
+                 is written next to itself with the extension .salexe,
+                 and stdin is compiled to stdout.
+  --keep-going   Write the executable even if some lines failed to compile.
+  -g, --debug-info
+                 Also write the source line of every instruction next to the
+                 executable, with the extension .saldbg, for the emulator's
+                 debugger. Not allowed when writing to stdout.
+  -q, --quiet    Only print errors.
+  -v, --verbose  Print the binary of every compiled instruction.
+  -h, --help     Print this message.
+
+Exit status:
+  0   everything compiled
+  1   at least one line failed to compile
+  2   an input could not be read or an output could not be written
+  64  the command line was not understood";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Options {
+    inputs: Vec<String>,
+    output: Option<String>,
+    keep_going: bool,
+    debug_info: bool,
+    verbosity: Verbosity,
+}
+
+/// Front end of `salc`. Takes the command line arguments, without the program name,
+/// and returns the exit status.
+///
+/// By default any error in any line means no executable is written, since
+/// every relative jump past a dropped line would land on the wrong instruction.
+/// With `--keep-going`, lines that failed are left out and the executable is
+/// written anyway. Either way all errors are reported, and the returned exit
+/// status is non-zero if there were any.
+pub fn compile(arguments: Vec<String>) -> i32 {
+    let options = match parse_arguments(arguments) {
+        Ok(Some(_options)) => _options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return SUCCESS;
+        }
+        Err(_message) => {
+            eprintln!("error: {}\n\n{}", _message, USAGE);
+            return USAGE_ERROR;
+        }
+    };
+
+    let mut status = SUCCESS;
+    for _input in &options.inputs {
+        let output = match &options.output {
+            Some(_output) => _output.clone(),
+            None => output_path(_input),
+        };
+        status = status.max(compile_file(_input, &output, &options));
+    }
+    status
+}
+
+// `Ok(None)` means help was asked for.
+fn parse_arguments(arguments: Vec<String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        inputs: Vec::new(),
+        output: None,
+        keep_going: false,
+        debug_info: false,
+        verbosity: Verbosity::Normal,
+    };
+
+    let mut arguments = arguments.into_iter();
+    while let Some(_argument) = arguments.next() {
+        match _argument.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-o" => match arguments.next() {
+                Some(_output) if options.output.is_none() => options.output = Some(_output),
+                Some(_) => return Err("-o given more than once".to_string()),
+                None => return Err("-o needs a file".to_string()),
+            },
+            "--keep-going" => options.keep_going = true,
+            "-g" | "--debug-info" => options.debug_info = true,
+            "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+            _flag if _flag.starts_with('-') && _flag != STDIO => {
+                return Err(format!("unknown option {}", _flag))
             }
+            _ => options.inputs.push(_argument),
+        }
+    }
 
-            println!("Done compiling!\nWriting to output...");
-            match fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(OUT_FILE_PATH)
-            {
-                Ok(mut _file) => match _file.write_all(&executable) {
-                    Ok(()) => {
-                        println!(
-                            "Done writing to output\nExecutable code found at: \"{}\"",
-                            OUT_FILE_PATH
-                        );
-                    }
-                    Err(_) => {
-                        println!("Failed to write file");
-                    }
-                },
-                Err(_) => {
-                    println!("Failed to open or create file");
+    if options.inputs.is_empty() {
+        return Err("a path to a SAL file must be provided".to_string());
+    }
+    if options.output.is_some() && options.inputs.len() > 1 {
+        return Err("-o can only be used with a single input".to_string());
+    }
+    // `salc program.salexe` would otherwise compile the executable as source and write over it.
+    for _input in options.inputs.iter().filter(|_input| *_input != STDIO) {
+        let output = options.output.clone().unwrap_or_else(|| output_path(_input));
+        if Path::new(&output) == Path::new(_input) {
+            return Err(format!("{} would be written over by its own executable", _input));
+        }
+    }
+    let to_stdout = match &options.output {
+        Some(_output) => _output == STDIO,
+        None => options.inputs.iter().any(|_input| _input == STDIO),
+    };
+    if options.debug_info && to_stdout {
+        return Err("--debug-info needs the executable to be written to a file".to_string());
+    }
+    Ok(Some(options))
+}
+
+// `program.sal` is compiled to `program.salexe`, stdin to stdout.
+fn output_path(input: &str) -> String {
+    if input == STDIO {
+        STDIO.to_string()
+    } else {
+        Path::new(input).with_extension(EXTENSION).to_string_lossy().to_string()
+    }
+}
+
+fn compile_file(input: &str, output: &str, options: &Options) -> i32 {
+    // Progress goes to stderr, so an executable written to stdout stays clean.
+    let normal = options.verbosity != Verbosity::Quiet;
+    let verbose = options.verbosity == Verbosity::Verbose;
+
+    let source = if input == STDIO {
+        let mut _source = String::new();
+        std::io::stdin().read_to_string(&mut _source).map(|_| _source)
+    } else {
+        fs::read_to_string(input)
+    };
+    let source = match source {
+        Ok(_source) => _source,
+        Err(_error) => {
+            eprintln!("Failed to read file {:?}: {}", input, _error);
+            return IO_ERROR;
+        }
+    };
+    if normal {
+        eprintln!("Compiling file: {:?}", input);
+    }
+
+    let (program, errors) = assemble(&source);
+    if normal && program.relaxed_bytes > 0 {
+        eprintln!("Added {} byte(s) of trampolines for branches out of range.", program.relaxed_bytes);
+    }
+    if verbose {
+        for (_line, _instruction) in program.lines.iter().zip(&program.bytes) {
+            eprintln!("{:?}:{:08b}", _line, _instruction);
+        }
+    }
+    if !errors.is_empty() {
+        eprint!("{}", errors.render(&source));
+        if !options.keep_going {
+            eprintln!("Failed to compile {:?}, found {} error(s). No executable was written.", input, errors.len());
+            return COMPILE_ERROR;
+        }
+    }
+
+    let written = if output == STDIO {
+        std::io::stdout().write_all(&program.bytes).and_then(|_| std::io::stdout().flush())
+    } else {
+        fs::write(output, &program.bytes)
+    };
+    match written {
+        Ok(()) if normal && output != STDIO => {
+            eprintln!("Executable code found at: {:?}", output)
+        }
+        Ok(()) => (),
+        Err(_error) => {
+            eprintln!("Failed to write {:?}: {}", output, _error);
+            return IO_ERROR;
+        }
+    }
+    if options.debug_info {
+        let debug_info = Path::new(output).with_extension(debug_info::DEBUG_INFO_EXTENSION);
+        if let Err(_error) = fs::write(&debug_info, debug_info::write_debug_info(&program.lines)) {
+            eprintln!("Failed to write {:?}: {}", debug_info, _error);
+            return IO_ERROR;
+        }
+    }
+
+    if !errors.is_empty() {
+        eprintln!("Kept going past {} error(s), the executable is missing those lines.", errors.len());
+        COMPILE_ERROR
+    } else {
+        SUCCESS
+    }
+}
+
+const DISASSEMBLER_USAGE: &str = "Usage: saldis [OPTIONS] <INPUT>
+
+Disassembles a SAL executable back into SAL source.
+Use - as the input to read from stdin.
+
+Options:
+  -o <FILE>      Write the source to FILE instead of stdout.
+  -h, --help     Print this message.
+
+Exit status:
+  0   the executable was disassembled
+  2   the input could not be read or the output could not be written
+  64  the command line was not understood";
+
+/// Front end of `saldis`. Takes the command line arguments, without the program name,
+/// and returns the exit status.
+pub fn disassemble_cli(arguments: Vec<String>) -> i32 {
+    let mut input: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut arguments = arguments.into_iter();
+    while let Some(_argument) = arguments.next() {
+        let error = match _argument.as_str() {
+            "-h" | "--help" => {
+                println!("{}", DISASSEMBLER_USAGE);
+                return SUCCESS;
+            }
+            "-o" => match arguments.next() {
+                Some(_output) if output.is_none() => {
+                    output = Some(_output);
+                    continue;
                 }
+                Some(_) => "-o given more than once".to_string(),
+                None => "-o needs a file".to_string(),
+            },
+            _flag if _flag.starts_with('-') && _flag != STDIO => format!("unknown option {}", _flag),
+            _ if input.is_none() => {
+                input = Some(_argument);
+                continue;
             }
+            _ => "only one input can be disassembled at a time".to_string(),
+        };
+        eprintln!("error: {}\n\n{}", error, DISASSEMBLER_USAGE);
+        return USAGE_ERROR;
+    }
+    let input = match input {
+        Some(_input) => _input,
+        None => {
+            eprintln!("error: a path to a SAL executable must be provided\n\n{}", DISASSEMBLER_USAGE);
+            return USAGE_ERROR;
+        }
+    };
+
+    let bytes = if input == STDIO {
+        let mut _bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut _bytes).map(|_| _bytes)
+    } else {
+        fs::read(&input)
+    };
+    let bytes = match bytes {
+        Ok(_bytes) => _bytes,
+        Err(_error) => {
+            eprintln!("Failed to read file {:?}: {}", input, _error);
+            return IO_ERROR;
+        }
+    };
+
+    let source = disassembler::disassemble(&bytes);
+    let written = match output.as_deref() {
+        None | Some(STDIO) => std::io::stdout().write_all(source.as_bytes()),
+        Some(_output) => fs::write(_output, source),
+    };
+    match written {
+        Ok(()) => SUCCESS,
+        Err(_error) => {
+            eprintln!("Failed to write output: {}", _error);
+            IO_ERROR
         }
-        Err(_) => println!("Failed to read file"),
     }
 }
 
-fn line_has_code(line: &str) -> bool {
-    !(line.is_empty() || line == line.to_lowercase())
+/// ## Program
+/// A compiled SAL program, one byte per instruction.
+///
+/// `lines[i]` is the 1-based source line that `bytes[i]` was compiled from.
+/// `relaxed_bytes` is how many of the bytes are trampolines added for
+/// branches that couldn't reach their labels, see `relaxation::relax`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Program {
+    pub bytes: Vec<u8>,
+    pub lines: Vec<usize>,
+    pub relaxed_bytes: usize,
+}
+
+/// Compile SAL source text in memory, without touching the file system or printing anything.
+///
+/// Fails with every diagnostic found if any line does not compile.
+pub fn compile_str(source: &str) -> Result<Program, Diagnostics> {
+    let (program, errors) = assemble(source);
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
+}
+
+// Compile every line that can be compiled, collecting the diagnostics of those that can't.
+fn assemble(source: &str) -> (Program, Diagnostics) {
+    let source = source::normalize(source);
+    let lines = source::lines(&source);
+
+    let mut statements: Vec<(usize, Statement)> = Vec::new();
+    let mut errors = Diagnostics::default();
+    for (_index, _line) in lines.iter().enumerate() {
+        match parser::parse(_line, _index + 1) {
+            Ok(Some(_statement)) => statements.push((_index, _statement)),
+            Ok(None) => (),
+            Err(_diagnostic) => errors.push(_diagnostic),
+        }
+    }
+
+    let relaxed_bytes = relaxation::relax(&mut statements);
+    let (labels, duplicates) = compiler::layout(&statements);
+    errors.0.extend(duplicates.0);
+
+    let mut program = Program { relaxed_bytes, ..Program::default() };
+    let instructions = statements.iter().filter_map(|(_index, _statement)| match _statement {
+        Statement::Instruction(_instruction) => Some((_index, _instruction)),
+        Statement::Label(_) => None,
+    });
+    for (_address, (_index, _instruction)) in instructions.enumerate() {
+        match compiler::encode(_instruction, &labels, _address, _index + 1) {
+            Ok(_byte) => {
+                program.bytes.push(_byte);
+                program.lines.push(_index + 1);
+            }
+            Err(_diagnostic) => errors.push(_diagnostic),
+        }
+    }
+    errors.0.sort_by_key(|_diagnostic| _diagnostic.line);
+    (program, errors)
 }
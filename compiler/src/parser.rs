@@ -0,0 +1,246 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - builds the syntax tree of a line out of its tokens
+ */
+use std::ops::Range;
+
+use crate::ast::{Instruction, Operation, Registry, Spanned, Statement};
+use crate::diagnostic::{nearest, Diagnostic, ErrorKind};
+use crate::lexer::{self, Keyword, OperationName, Token, TokenKind};
+
+/// Lines without any capital letters are comments.
+pub fn line_has_code(line: &str) -> bool {
+    !(line.is_empty() || line == line.to_lowercase())
+}
+
+/// Parse one source line. `Ok(None)` means the line has no code on it.
+///
+/// `line_number` is 1-based and only used for diagnostics.
+pub fn parse(line: &str, line_number: usize) -> Result<Option<Statement>, Diagnostic> {
+    if !line_has_code(line) {
+        return Ok(None);
+    }
+    let tokens = lexer::lex(line);
+    let terminator = tokens
+        .iter()
+        .find(|_token| matches!(_token.kind, TokenKind::Period | TokenKind::Colon))
+        .map(|_token| _token.kind);
+    let parser = Parser { line, line_number, terminator, segments: segments(&tokens) };
+    parser.statement().map(Some)
+}
+
+// The tokens between two commas, and where they are in the line.
+struct Segment<'a> {
+    tokens: &'a [Token],
+    span: Range<usize>,
+}
+
+struct Parser<'a> {
+    line: &'a str,
+    line_number: usize,
+    terminator: Option<TokenKind>,
+    segments: Vec<Segment<'a>>,
+}
+
+// Group the tokens of a statement by the commas between them, dropping the terminator and any comment.
+fn segments(tokens: &[Token]) -> Vec<Segment<'_>> {
+    let end = tokens
+        .iter()
+        .position(|_token| matches!(_token.kind, TokenKind::Period | TokenKind::Colon))
+        .unwrap_or(tokens.len());
+
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut position = 0;
+    for (_index, _token) in tokens[..end].iter().enumerate() {
+        if _token.kind == TokenKind::Comma {
+            segments.push(Segment { tokens: &tokens[start.._index], span: position.._token.span.start });
+            start = _index + 1;
+            position = _token.span.end;
+        }
+    }
+    let end_position = tokens.get(end).map(|_token| _token.span.start).unwrap_or(position);
+    let last = Segment { tokens: &tokens[start..end], span: position..end_position };
+    if !last.tokens.is_empty() || !segments.is_empty() {
+        segments.push(last);
+    }
+
+    // Narrow every span down to its tokens, so diagnostics don't point at surrounding spaces.
+    for _segment in segments.iter_mut() {
+        if let (Some(_first), Some(_last)) = (_segment.tokens.first(), _segment.tokens.last()) {
+            _segment.span = _first.span.start.._last.span.end;
+        }
+    }
+    segments
+}
+
+impl<'a> Parser<'a> {
+    fn statement(&self) -> Result<Statement, Diagnostic> {
+        if self.terminator == Some(TokenKind::Colon) && self.segments.len() <= 1 {
+            let label = self.label(0, 0)?;
+            return match label.value.split(' ').collect::<Vec<&str>>()[..] {
+                ["THE", _, ..] => Ok(Statement::Label(label)),
+                _ => {
+                    let name = label.value.trim_start_matches("THE").trim_start();
+                    let hint = Some(format!("THE {}", name)).filter(|_| !name.is_empty());
+                    Err(self.diagnostic(ErrorKind::UnexpectedPhrase, label.span).with_hint(hint))
+                }
+            };
+        }
+
+        let prefix = match self.keyword(0, "a prefix")? {
+            Some(Keyword::Prefix(_prefix)) => _prefix,
+            _ => return Err(self.unknown(ErrorKind::UnknownPrefix, 0, &prefix_phrases())),
+        };
+        let operation_name = match self.keyword(1, "an operation")? {
+            Some(Keyword::Operation(_operation)) => _operation,
+            _ => return Err(self.unknown(ErrorKind::UnknownOperation, 1, &operation_phrases())),
+        };
+
+        let (operation, length) = match operation_name {
+            OperationName::Increment => {
+                let registry = self.registry(2)?;
+                let negatively = self.choice(3, "an argument", [Keyword::Positively, Keyword::Negatively])?;
+                let using_other_registry = self.choice(4, "an argument", [Keyword::UsingOne, Keyword::UsingTheOtherRegistry])?;
+                (Operation::Increment { registry, negatively, using_other_registry }, 5)
+            }
+            OperationName::To => {
+                let registry = self.registry(2)?;
+                let action = match self.keyword(3, "an argument")? {
+                    Some(Keyword::StackAction(_action)) => _action,
+                    _ => return Err(self.unknown(ErrorKind::UnknownArgument, 3, &stack_action_phrases())),
+                };
+                (Operation::To { registry, action }, 4)
+            }
+            OperationName::Access => {
+                let registry = self.registry(2)?;
+                let outputting = self.choice(3, "an argument", [Keyword::InputtingAValue, Keyword::OutputtingAValue])?;
+                let as_character = self.choice(4, "an argument", [Keyword::AsAnInteger, Keyword::AsACharacter])?;
+                (Operation::Access { registry, outputting, as_character }, 5)
+            }
+            OperationName::Repeat => {
+                let registry = self.registry(2)?;
+                let ending = self.choice(3, "an argument", [Keyword::StartingHere, Keyword::EndingHere])?;
+                let second_loop = self.choice(4, "an argument", [Keyword::FirstLoop, Keyword::SecondLoop])?;
+                (Operation::Repeat { registry, ending, second_loop }, 5)
+            }
+            OperationName::BranchIfGreater => {
+                let registry = self.registry(2)?;
+                (Operation::BranchIfGreater { registry, label: self.branch_label(3)? }, 4)
+            }
+            OperationName::BranchIfZero => {
+                let registry = self.registry(2)?;
+                (Operation::BranchIfZero { registry, label: self.branch_label(3)? }, 4)
+            }
+            OperationName::JumpIfEqual => (Operation::JumpIfEqual { label: self.label(2, 0)? }, 3),
+            OperationName::Jump => (Operation::Jump { label: self.label(2, 0)? }, 3),
+        };
+
+        if let Some(_extra) = self.segments.get(length) {
+            return Err(self.diagnostic(ErrorKind::UnexpectedPhrase, _extra.span.clone()));
+        }
+        Ok(Statement::Instruction(Instruction { prefix, operation }))
+    }
+
+    fn segment(&self, index: usize, expected: &'static str) -> Result<&Segment<'a>, Diagnostic> {
+        match self.segments.get(index) {
+            Some(_segment) if !_segment.tokens.is_empty() => Ok(_segment),
+            Some(_segment) => Err(self.diagnostic(ErrorKind::Missing(expected), _segment.span.clone())),
+            None => {
+                let end = self.segments.last().map(|_segment| _segment.span.end).unwrap_or(0);
+                Err(self.diagnostic(ErrorKind::Missing(expected), end..end))
+            }
+        }
+    }
+
+    // A segment that should be exactly one keyword, `None` if it is anything else.
+    fn keyword(&self, index: usize, expected: &'static str) -> Result<Option<Keyword>, Diagnostic> {
+        let segment = self.segment(index, expected)?;
+        match segment.tokens {
+            [Token { kind: TokenKind::Keyword(_keyword), .. }] => Ok(Some(*_keyword)),
+            _ => Ok(None),
+        }
+    }
+
+    // `THE FIRST REGISTRY`, optionally preceded by `COMPARING`.
+    fn registry(&self, index: usize) -> Result<Registry, Diagnostic> {
+        let segment = self.segment(index, "a registry")?;
+        let tokens = match segment.tokens {
+            [Token { kind: TokenKind::Keyword(Keyword::Comparing), .. }, _rest @ ..] => _rest,
+            _tokens => _tokens,
+        };
+        match tokens {
+            [Token { kind: TokenKind::Keyword(Keyword::Registry(_registry)), .. }] => Ok(*_registry),
+            _ => Err(self.unknown(ErrorKind::UnknownRegistry, index, &registry_phrases())),
+        }
+    }
+
+    // One of two keywords, `false` for the first and `true` for the second.
+    fn choice(&self, index: usize, expected: &'static str, options: [Keyword; 2]) -> Result<bool, Diagnostic> {
+        let keyword = self.keyword(index, expected)?;
+        match options.iter().position(|_option| Some(*_option) == keyword) {
+            Some(_position) => Ok(_position == 1),
+            None => Err(self.unknown(ErrorKind::UnknownArgument, index, &options.map(lexer::phrase))),
+        }
+    }
+
+    // `AND JUMPING TO THE END`
+    fn branch_label(&self, index: usize) -> Result<Spanned<String>, Diagnostic> {
+        let segment = self.segment(index, "`AND JUMPING TO` and a label")?;
+        match segment.tokens.first() {
+            Some(Token { kind: TokenKind::Keyword(Keyword::AndJumpingTo), .. }) => self.label(index, 1),
+            _ => Err(self.unknown(ErrorKind::UnknownArgument, index, &[lexer::phrase(Keyword::AndJumpingTo)])),
+        }
+    }
+
+    // The words of a segment, from its `skip`th token on, taken as a label name whatever they are.
+    fn label(&self, index: usize, skip: usize) -> Result<Spanned<String>, Diagnostic> {
+        let segment = self.segment(index, "a label")?;
+        match (segment.tokens.get(skip), segment.tokens.last()) {
+            (Some(_first), Some(_last)) => {
+                let span = _first.span.start.._last.span.end;
+                let name = self.line[span.clone()].split_whitespace().collect::<Vec<&str>>().join(" ");
+                Ok(Spanned { value: name, span })
+            }
+            _ => {
+                let end = segment.span.end;
+                Err(self.diagnostic(ErrorKind::Missing("a label"), end..end))
+            }
+        }
+    }
+
+    fn unknown(&self, kind: ErrorKind, index: usize, candidates: &[&str]) -> Diagnostic {
+        let span = self.segments[index].span.clone();
+        let phrase = &self.line[span.clone()];
+        self.diagnostic(kind, span).with_hint(nearest(phrase, candidates).map(|_hint| _hint.to_string()))
+    }
+
+    fn diagnostic(&self, kind: ErrorKind, span: Range<usize>) -> Diagnostic {
+        let phrase = &self.line[span.clone()];
+        Diagnostic::new(kind, self.line_number, span, phrase)
+    }
+}
+
+fn phrases(filter: fn(&Keyword) -> bool) -> Vec<&'static str> {
+    lexer::KEYWORDS
+        .iter()
+        .filter(|(_, _keyword)| filter(_keyword))
+        .map(|(_phrase, _)| *_phrase)
+        .collect()
+}
+
+fn prefix_phrases() -> Vec<&'static str> {
+    phrases(|_keyword| matches!(_keyword, Keyword::Prefix(_)))
+}
+
+fn operation_phrases() -> Vec<&'static str> {
+    phrases(|_keyword| matches!(_keyword, Keyword::Operation(_)))
+}
+
+fn registry_phrases() -> Vec<&'static str> {
+    phrases(|_keyword| matches!(_keyword, Keyword::Registry(_)))
+}
+
+fn stack_action_phrases() -> Vec<&'static str> {
+    phrases(|_keyword| matches!(_keyword, Keyword::StackAction(_)))
+}
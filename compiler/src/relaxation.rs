@@ -0,0 +1,164 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - rewrites branches to labels out of their reach into chains of jumps
+ */
+use crate::ast::{Instruction, Operation, Prefix, Spanned, Statement};
+use crate::compiler;
+
+/// Bytes each trampoline adds.
+const ISLAND_LENGTH: usize = 1;
+
+/// ## Relaxation
+/// A branch only has two or three bits for the distance to its label, so it
+/// can reach at most 5 or 9 instructions ahead, or 3 or 7 behind.
+/// Every branch further from its label than that is pointed at a trampoline
+/// instead: a `JUMP TO` placed as far towards the label as the branch reaches,
+/// which jumps on to the label (or to the next trampoline, if it is still too far).
+///
+/// Every instruction changes how the machine feels, so the code running past a
+/// trampoline must never get to it. Trampolines only go right after a `JUMP TO`
+/// the machine never refuses, one that isn't asked for with `PLEASE`: it either
+/// jumps or ends the run, so nothing falls through to what comes after it.
+/// For the same reason trampolines themselves are never asked for with `PLEASE`:
+/// they jump forward with `I'M BEGGING YOU` and back with `NOW`. A branch that is
+/// taken does run its trampolines, which the machine feels like any other jump.
+///
+/// A trampoline moves every label after it one address further, which can push
+/// another jump across it out of reach in turn. A trampoline is only kept if the
+/// jumps end up closer to their labels than before, otherwise its branch is given up on.
+///
+/// Branches with nowhere in reach to put a trampoline that helps, to a label in
+/// the wrong direction for their prefix, or to a label that doesn't exist, are
+/// left for `compiler::encode` to report.
+///
+/// `statements` are paired with the line they came from, and each trampoline is
+/// given the line of the branch it was made for. Returns the number of bytes added.
+pub fn relax(statements: &mut Vec<(usize, Statement)>) -> usize {
+    let mut added: usize = 0;
+    let mut trampolines: usize = 0;
+    let mut given_up: Vec<usize> = Vec::new();
+    // Every pass lowers the excess or gives up on a branch, so this is only a safeguard.
+    let passes = 2 * excess(statements) + statements.len();
+    for _ in 0..passes {
+        let (_branch, _island_address) = match first_out_of_reach(statements, &given_up) {
+            Some(_found) => _found,
+            None => break,
+        };
+        let (labels, _) = compiler::layout(statements);
+        trampolines += 1;
+        let mut name = format!("THE TRAMPOLINE {}", trampolines);
+        while labels.contains_key(&name) {
+            trampolines += 1;
+            name = format!("THE TRAMPOLINE {}", trampolines);
+        }
+
+        let mut relaxed = statements.clone();
+        let (line, prefix, target) = match &mut relaxed[_branch] {
+            (_line, Statement::Instruction(_instruction)) => {
+                let label = _instruction.operation.label_mut().expect("only branches are out of reach");
+                let target = std::mem::replace(&mut label.value, name.clone());
+                (*_line, _instruction.prefix, target)
+            }
+            (_, Statement::Label(_)) => unreachable!("only instructions are out of reach"),
+        };
+        let trampoline = if prefix.is_polite() { Prefix::Begging } else { Prefix::Now };
+
+        let island = vec![(line, Statement::Label(spanned(&name))), (line, jump(trampoline, &target))];
+        let at = insertion_index(&relaxed, _island_address);
+        let island_statements = island.len();
+        relaxed.splice(at..at, island);
+
+        if excess(&relaxed) < excess(statements) {
+            *statements = relaxed;
+            given_up.iter_mut().filter(|_index| **_index >= at).for_each(|_index| *_index += island_statements);
+            added += ISLAND_LENGTH;
+        } else {
+            given_up.push(_branch);
+        }
+    }
+    added
+}
+
+// How many addresses past their reach all the jumps are, put together.
+fn excess(statements: &[(usize, Statement)]) -> usize {
+    let (labels, _) = compiler::layout(statements);
+    instructions(statements)
+        .enumerate()
+        .filter_map(|(_address, _instruction)| {
+            let target = labels.get(&_instruction.operation.label()?.value)?;
+            let distance = sal_isa::distance(_instruction.prefix, _address, *target)?;
+            Some(distance.saturating_sub(compiler::distance_max(&_instruction.operation)? as usize))
+        })
+        .sum()
+}
+
+fn instructions(statements: &[(usize, Statement)]) -> impl Iterator<Item = &Instruction> {
+    statements.iter().filter_map(|(_, _statement)| match _statement {
+        Statement::Instruction(_instruction) => Some(_instruction),
+        Statement::Label(_) => None,
+    })
+}
+
+// The statement index of the first branch not given up on that can't reach its label but can
+// reach somewhere to put a trampoline, and the address the trampoline has to be put at.
+fn first_out_of_reach(statements: &[(usize, Statement)], given_up: &[usize]) -> Option<(usize, usize)> {
+    let (labels, _) = compiler::layout(statements);
+    let instructions: Vec<&Instruction> = instructions(statements).collect();
+    // Whether a trampoline at `address` would be out of the way of the code before it.
+    let out_of_the_way = |address: usize| {
+        address > 0 && matches!(instructions[address - 1], Instruction { prefix, operation: Operation::Jump { .. } } if *prefix != Prefix::Please)
+    };
+
+    let mut address: usize = 0;
+    for (_index, (_, _statement)) in statements.iter().enumerate() {
+        let instruction = match _statement {
+            Statement::Instruction(_instruction) => _instruction,
+            Statement::Label(_) => continue,
+        };
+        let label = instruction.operation.label().filter(|_| !given_up.contains(&_index));
+        if let (Some(_label), Some(_distance_max)) = (label, compiler::distance_max(&instruction.operation)) {
+            if let Some(_target) = labels.get(&_label.value) {
+                let reach = _distance_max as usize + 1;
+                let polite = instruction.prefix.is_polite();
+                // Forward jumps land `distance + 2` ahead, so the trampoline goes at most `reach + 1` ahead,
+                // as close to the label as can be.
+                if polite && *_target > address + reach + 1 {
+                    if let Some(_island_address) = (address + 2..=address + reach + 1).rev().find(|_address| out_of_the_way(*_address)) {
+                        return Some((_index, _island_address));
+                    }
+                }
+                // Backward jumps land `distance` behind, and the trampoline pushes the branch 1 further away.
+                if !polite && *_target + reach <= address {
+                    if let Some(_island_address) = (address + ISLAND_LENGTH + 1 - reach..=address).find(|_address| out_of_the_way(*_address)) {
+                        return Some((_index, _island_address));
+                    }
+                }
+            }
+        }
+        address += 1;
+    }
+    None
+}
+
+// Where to insert a trampoline so it ends up at `address`.
+// Labels pointing at that address keep pointing at the instruction they were written before.
+fn insertion_index(statements: &[(usize, Statement)], address: usize) -> usize {
+    let mut current: usize = 0;
+    for (_index, (_, _statement)) in statements.iter().enumerate() {
+        if current == address {
+            return _index;
+        }
+        if let Statement::Instruction(_) = _statement {
+            current += 1;
+        }
+    }
+    statements.len()
+}
+
+fn jump(prefix: Prefix, label: &str) -> Statement {
+    Statement::Instruction(Instruction { prefix, operation: Operation::Jump { label: spanned(label) } })
+}
+
+fn spanned(label: &str) -> Spanned<String> {
+    Spanned { value: label.to_string(), span: 0..0 }
+}
@@ -0,0 +1,49 @@
+/***
+ * Compiler for The Socially Acceptable Language
+ * - normalizes source text before it is parsed
+ */
+
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+// Characters that editors and word processors like to put in place of an apostrophe.
+const APOSTROPHES: [char; 7] = ['’', '‘', 'ʼ', '′', '`', '´', '‛'];
+const QUOTES: [char; 7] = ['“', '”', '„', '‟', '″', '«', '»'];
+
+/// Bring source text into the one form the parser understands:
+/// * no byte order mark
+/// * lines separated by `\n`, whether the file used LF, CRLF or CR
+/// * tabs and repeated spaces collapsed to a single space, and no space at either end of a line
+/// * every kind of apostrophe written as `'` and every kind of quote as `"`
+///
+/// Diagnostics point into the normalized text, so render them against it too.
+pub fn normalize(source: &str) -> String {
+    let source = source.strip_prefix(BYTE_ORDER_MARK).unwrap_or(source);
+    source
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .split('\n')
+        .map(normalize_line)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn normalize_line(line: &str) -> String {
+    line.split_whitespace()
+        .map(|_word| {
+            _word
+                .chars()
+                .map(|_char| match _char {
+                    _apostrophe if APOSTROPHES.contains(&_apostrophe) => '\'',
+                    _quote if QUOTES.contains(&_quote) => '"',
+                    _ => _char,
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// The lines of already normalized source.
+pub fn lines(normalized: &str) -> Vec<&str> {
+    normalized.split('\n').collect()
+}
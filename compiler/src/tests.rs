@@ -1,36 +1,329 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use std::collections::HashMap;
-    use super::super::compile;
+    use super::super::{compile, compile_str};
+    use crate::ast::Statement;
     use crate::compiler;
+    use crate::diagnostic::{Diagnostic, ErrorKind};
+    use crate::parser::parse;
+
+    // Compile the instruction on one line, placed at `address`.
+    fn encode(line: &str, labels: &HashMap<String, usize>, address: usize) -> Result<u8, Diagnostic> {
+        match parse(line, address + 1)? {
+            Some(Statement::Instruction(_instruction)) => compiler::encode(&_instruction, labels, address, address + 1),
+            _other => panic!("{:?}", _other),
+        }
+    }
 
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
     #[test]
-    fn test() {
+    fn diagnostic_points_at_operation() {
         let hm: HashMap<String, usize> = HashMap::new();
-        println!(
-            "{:?}",
-            compiler::run(
-                "PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER. input x", &hm, &0
-            )
-        );
+        let line = "PLEASE, ACCES, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.";
+        let diagnostic = encode(line, &hm, 4).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::UnknownOperation);
+        assert_eq!(diagnostic.line, 5);
+        assert_eq!(diagnostic.span, 8..13);
+        assert_eq!(diagnostic.phrase, "ACCES");
+        assert_eq!(diagnostic.hint.as_deref(), Some("ACCESS"));
+        assert!(diagnostic.render(line).contains("        ^^^^^\n"));
     }
 
     #[test]
-    fn finding_label() {
-        let slice = match compiler::find_labels("THE START: label") {
-            Some(_index) => &"THE START: label"[.._index],
-            None => ""
-        };
-        assert_eq!("THE START", slice)
+    fn diagnostic_for_labels() {
+        let mut hm: HashMap<String, usize> = HashMap::new();
+        hm.insert("THE START".to_string(), 0);
+        let diagnostic = encode("NOW, JUMP TO, THE STRAT.", &hm, 2).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::UnknownLabel);
+        assert_eq!(diagnostic.phrase, "THE STRAT");
+        assert_eq!(diagnostic.hint.as_deref(), Some("THE START"));
+
+        let diagnostic = encode("PLEASE, JUMP TO, THE START.", &hm, 2).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::LabelWrongDirection);
+        assert_eq!(diagnostic.hint, None);
+
+        let diagnostic = encode("NOW, JUMP TO, THE START.", &hm, 8).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::LabelOutOfRange);
+
+        hm.insert("THE END".to_string(), 3);
+        let diagnostic = encode("NOW, JUMP TO, THE END.", &hm, 2).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::LabelWrongDirection);
+        assert_eq!(diagnostic.hint, None);
     }
 
     #[test]
     fn compiler_test() {
-        let args: Vec<String> = vec!("./input.sal".to_string());
-        compile(args)
+        let output = std::env::temp_dir().join("sal_compiler_test.salexe");
+        let args: Vec<String> = vec!(
+            "./input.sal".to_string(),
+            "-o".to_string(),
+            output.to_string_lossy().to_string(),
+            "--quiet".to_string(),
+        );
+        assert_eq!(compile(args), crate::SUCCESS);
+        assert!(!std::fs::read(&output).unwrap().is_empty());
+
+        let with_debug_info = std::env::temp_dir().join("sal_compiler_test_debug_info.salexe");
+        let args: Vec<String> =
+            ["./input.sal", "-o", &with_debug_info.to_string_lossy(), "-q", "-g"].iter().map(|_arg| _arg.to_string()).collect();
+        assert_eq!(compile(args), crate::SUCCESS);
+        let lines = std::fs::read_to_string(with_debug_info.with_extension("saldbg")).unwrap();
+        let lines = crate::debug_info::read_debug_info(&lines).unwrap();
+        assert_eq!(lines.len(), std::fs::read(&with_debug_info).unwrap().len());
+        assert!(lines.windows(2).all(|_pair| _pair[0] <= _pair[1]));
+    }
+
+    #[test]
+    fn command_line() {
+        let args = |_args: &[&str]| compile(_args.iter().map(|_arg| _arg.to_string()).collect());
+        assert_eq!(args(&[]), crate::USAGE_ERROR);
+        assert_eq!(args(&["--frobnicate", "./input.sal"]), crate::USAGE_ERROR);
+        assert_eq!(args(&["a.sal", "b.sal", "-o", "out.salexe"]), crate::USAGE_ERROR);
+        assert_eq!(args(&["--debug-info", "-"]), crate::USAGE_ERROR);
+        assert_eq!(args(&["-g", "a.sal", "-o", "-"]), crate::USAGE_ERROR);
+        assert_eq!(args(&["program.salexe"]), crate::USAGE_ERROR);
+        assert_eq!(args(&["program.sal", "-o", "program.sal"]), crate::USAGE_ERROR);
+        assert_eq!(args(&["--help"]), crate::SUCCESS);
+        assert_eq!(args(&["./does-not-exist.sal"]), crate::IO_ERROR);
+        assert_eq!(crate::output_path("dir/program.sal"), "dir/program.salexe");
+        assert_eq!(crate::output_path("-"), "-");
+    }
+
+    #[test]
+    fn compile_fails_on_any_error() {
+        let path = std::env::temp_dir().join("sal_compile_fails_on_any_error.sal");
+        std::fs::write(&path, "PLEASE, JUMP TO, THE NOWHERE.\r\n").unwrap();
+        let output = path.with_extension("salexe");
+        let _ = std::fs::remove_file(&output);
+        let args: Vec<String> = vec!(path.to_string_lossy().to_string(), "-q".to_string());
+        assert_eq!(compile(args), crate::COMPILE_ERROR);
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn labelled_programs_compile_strictly() {
+        let path = std::env::temp_dir().join("sal_labelled_programs_compile_strictly.sal");
+        let source = std::fs::read_to_string("./test.sal").unwrap();
+        assert!(source.lines().any(|_line| matches!(parse(_line, 1), Ok(Some(Statement::Label(_))))));
+        std::fs::write(&path, source.lines().collect::<Vec<&str>>().join("\r\n")).unwrap();
+        let output = path.with_extension("salexe");
+        let _ = std::fs::remove_file(&output);
+        let args: Vec<String> = vec!(path.to_string_lossy().to_string(), "-q".to_string());
+        assert_eq!(compile(args), crate::SUCCESS);
+        assert!(!std::fs::read(&output).unwrap().is_empty());
+    }
+
+    #[test]
+    fn compile_str_in_memory() {
+        let source = "PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER. input x\r\n\r\nNOW, TO, THE SECOND REGISTRY, PUSH THE VALUE.";
+        let program = compile_str(source).unwrap();
+        assert_eq!(program.bytes, vec![0b10010000, 0b01001101]);
+        assert_eq!(program.lines, vec![1, 3]);
+    }
+
+    #[test]
+    fn compile_str_collects_every_error() {
+        let source = "PLEASE, ACCES, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\r\nNOW, TO, THE THIRD REGISTRY, PUSH THE VALUE.";
+        let errors = compile_str(source).unwrap_err();
+        let kinds: Vec<ErrorKind> = errors.iter().map(|_diagnostic| _diagnostic.kind).collect();
+        assert_eq!(kinds, vec![ErrorKind::UnknownOperation, ErrorKind::UnknownRegistry]);
+        assert!(errors.render(source).contains("2 | NOW, TO, THE THIRD REGISTRY, PUSH THE VALUE."));
+    }
+
+    #[test]
+    fn parses_typed_instructions() {
+        use crate::ast::{Instruction, Operation, Prefix, Registry};
+
+        let statement = parse("I'M BEGGING YOU, INCREMENT, THE SECOND REGISTRY, NEGATIVELY, USING ONE. y, x = y - 1, x.", 1);
+        assert_eq!(statement, Ok(Some(Statement::Instruction(Instruction {
+            prefix: Prefix::Begging,
+            operation: Operation::Increment { registry: Registry::Second, negatively: true, using_other_registry: false },
+        }))));
+
+        match parse("PLEASE, IF THE SPECIFIED REGISTRY IS EQUAL TO ZERO JUMP TO THE SPECIFIED LABEL, COMPARING THE FIRST REGISTRY, AND JUMPING TO THE END.", 1) {
+            Ok(Some(Statement::Instruction(_instruction))) => {
+                assert_eq!(_instruction.operation.label().unwrap().value, "THE END");
+            }
+            _other => panic!("{:?}", _other),
+        }
+        match parse("THE END: the end, for real.", 1) {
+            Ok(Some(Statement::Label(_label))) => assert_eq!(_label.value, "THE END"),
+            _other => panic!("{:?}", _other),
+        }
+        assert_eq!(parse("just a comment, nothing else.", 1), Ok(None));
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        let diagnostic = parse("NOW, JUMP TO.", 3).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::Missing("a label"));
+        let diagnostic = parse("PLEASE, TO", 3).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::Missing("a registry"));
+        assert_eq!(diagnostic.span, 10..10);
+        let diagnostic = parse("NOW, TO, THE FIRST REGISTRY, PUSH THE VALUE, PLEASE.", 3).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::UnexpectedPhrase);
+        assert_eq!(diagnostic.phrase, "PLEASE");
+        let diagnostic = parse("NOW, INCREMENT, THE FIRST REGISTRY, POSITIVELY, AS AN INTEGER.", 3).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::UnknownArgument);
+        let diagnostic = parse("NOTE: this is a comment", 3).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::UnexpectedPhrase);
+        assert_eq!(diagnostic.phrase, "NOTE");
+        assert_eq!(diagnostic.hint.as_deref(), Some("THE NOTE"));
+        assert_eq!(parse("THE: the end", 3).unwrap_err().kind, ErrorKind::UnexpectedPhrase);
+    }
+
+    #[test]
+    fn truncated_lines_never_panic() {
+        let source = std::fs::read_to_string("./test.sal").unwrap();
+        for _line in source.lines() {
+            for (_index, _) in _line.char_indices() {
+                let _ = compile_str(&_line[.._index]);
+            }
+        }
+    }
+
+    #[test]
+    fn normalizes_source() {
+        use crate::source::normalize;
+
+        assert_eq!(normalize("\u{feff}A\r\nB\rC\nD"), "A\nB\nC\nD");
+        assert_eq!(normalize("  NOW,\tTO,  THE   FIRST REGISTRY  "), "NOW, TO, THE FIRST REGISTRY");
+        assert_eq!(normalize("I’M ‘BEGGING’ “YOU”"), "I'M 'BEGGING' \"YOU\"");
+
+        let unix = "NOW, TO, THE FIRST REGISTRY, PUSH THE VALUE.\nI‘M\tBEGGING YOU, TO, THE FIRST REGISTRY, POP THE VALUE.";
+        let windows = "\u{feff}NOW, TO, THE FIRST REGISTRY, PUSH THE VALUE.\r\nI’M BEGGING  YOU, TO, THE FIRST REGISTRY, POP THE VALUE.";
+        assert_eq!(compile_str(unix).unwrap().bytes.len(), 2);
+        assert_eq!(compile_str(unix), compile_str(windows));
+    }
+
+    #[test]
+    fn debug_info_round_trips() {
+        use crate::debug_info::{read_debug_info, write_debug_info};
+
+        let lines = vec![1, 2, 2, 7, 12];
+        assert_eq!(write_debug_info(&lines), "1\n2\n2\n7\n12\n");
+        assert_eq!(read_debug_info(&write_debug_info(&lines)), Some(lines));
+        assert_eq!(read_debug_info(""), Some(Vec::new()));
+        assert_eq!(read_debug_info("1\nline 2\n"), None);
+    }
+
+    #[test]
+    fn labels_resolve_to_instruction_addresses() {
+        let source = "PLEASE, JUMP TO, THE END.\nNOW, TO, THE FIRST REGISTRY, PUSH THE VALUE.\nTHE END:\nTHE START:\nNOW, TO, THE FIRST REGISTRY, POP THE VALUE.\nNOW, JUMP TO, THE START.";
+        let program = compile_str(source).unwrap();
+        assert_eq!(program.bytes, vec![0b10111000, 0b01001001, 0b01001011, 0b01111001]);
+
+        let commented = "PLEASE, JUMP TO, THE END.\n\njust a comment\nNOW, TO, THE FIRST REGISTRY, PUSH THE VALUE.\nTHE END: the end\n\nTHE START:\nNOW, TO, THE FIRST REGISTRY, POP THE VALUE.\nwhere we jump back\nNOW, JUMP TO, THE START.";
+        assert_eq!(compile_str(commented).unwrap().bytes, program.bytes);
+    }
+
+    #[test]
+    fn duplicate_labels_are_errors() {
+        let source = "THE START:\nNOW, TO, THE FIRST REGISTRY, PUSH THE VALUE.\nTHE START: again\nNOW, JUMP TO, THE START.";
+        let errors = compile_str(source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let diagnostic = &errors.0[0];
+        assert_eq!(diagnostic.kind, ErrorKind::DuplicateLabel);
+        assert_eq!((diagnostic.line, diagnostic.span.clone()), (3, 0..9));
+        assert_eq!(diagnostic.previous.as_deref(), Some(&(1, 0..9)));
+        assert!(errors.render(source).contains("= note: defined before on line 1, column 1\n"));
+        assert_eq!(errors.to_string(), "3:1: error: label is already defined `THE START`, defined before at 1:1\n");
+    }
+
+    // Follow unconditional jumps from `pc` to the first other instruction, the way the emulator would.
+    fn follow_jumps(bytes: &[u8], pc: usize) -> usize {
+        let mut pc = pc;
+        while (bytes[pc] >> 3) & 0b111 == 0b111 {
+            let distance = (bytes[pc] & 0b111) as usize;
+            pc = if bytes[pc] >> 7 == 1 { pc + distance + 2 } else { pc - distance };
+        }
+        pc
+    }
+
+    const DOUBLE: &str = "NOW, TO, THE FIRST REGISTRY, DOUBLE THE VALUE.\n";
+    const POP: &str = "NOW, TO, THE SECOND REGISTRY, POP THE VALUE.\n";
+    const PUSH: &str = "NOW, TO, THE SECOND REGISTRY, PUSH THE VALUE.\n";
+
+    // Blocks of code each jumping over a `PUSH` no one runs, leaving somewhere to put trampolines.
+    fn blocks(count: usize) -> String {
+        (1..=count).map(|_block| format!("I'M BEGGING YOU, JUMP TO, THE BLOCK {}.\n{}THE BLOCK {}:\n{}", _block, PUSH, _block, DOUBLE)).collect()
+    }
+
+    #[test]
+    fn relaxes_far_forward_jumps() {
+        let source = format!("PLEASE, JUMP TO, THE END.\n{}THE END:\n{}", blocks(7), POP);
+        let program = compile_str(&source).unwrap();
+        assert_eq!(program.relaxed_bytes, 2);
+        assert_eq!(program.bytes.len(), 23 + program.relaxed_bytes);
+        assert_eq!(program.bytes[follow_jumps(&program.bytes, 0)], 0b01001111);
+
+        // Running straight through, the trampolines are jumped over with the code no one runs.
+        let mut executed = Vec::new();
+        let mut pc = 1;
+        while pc < program.bytes.len() {
+            pc = follow_jumps(&program.bytes, pc);
+            executed.push(program.bytes[pc]);
+            pc += 1;
+        }
+        assert_eq!(executed, [vec![0b01001000; 7], vec![0b01001111]].concat());
+    }
+
+    #[test]
+    fn relaxes_far_backward_jumps_and_branches() {
+        let source = format!("THE START:\n{}{}NOW, JUMP TO, THE START.\n", POP, blocks(7));
+        let program = compile_str(&source).unwrap();
+        assert!(program.relaxed_bytes > 0);
+        assert_eq!(follow_jumps(&program.bytes, program.bytes.len() - 1), 0);
+
+        let source = format!(
+            "PLEASE, IF THE SPECIFIED REGISTRY IS EQUAL TO ZERO JUMP TO THE SPECIFIED LABEL, COMPARING THE FIRST REGISTRY, AND JUMPING TO THE END.\n{}THE END:\n{}",
+            blocks(2),
+            POP
+        );
+        let program = compile_str(&source).unwrap();
+        assert_eq!(program.relaxed_bytes, 1);
+        let taken = 2 + (program.bytes[0] & 0b11) as usize;
+        assert_eq!(program.bytes[follow_jumps(&program.bytes, taken)], 0b01001111);
+    }
+
+    #[test]
+    fn gives_up_on_trampolines_that_push_other_jumps_out_of_reach() {
+        // Every trampoline for one jump to the end pushes the trampoline before it out of reach.
+        let source = format!(
+            "I'M BEGGING YOU, JUMP TO, THE END.\nPLEASE, JUMP TO, THE END.\nI'M BEGGING YOU, JUMP TO, THE END.\n{}THE END:\n{}",
+            DOUBLE.repeat(7),
+            DOUBLE
+        );
+        let errors = compile_str(&source).unwrap_err();
+        assert_eq!(errors.iter().map(|_diagnostic| (_diagnostic.line, _diagnostic.kind)).collect::<Vec<_>>(), [(1, ErrorKind::LabelOutOfRange)]);
+    }
+
+    #[test]
+    fn leaves_far_jumps_with_nowhere_to_put_a_trampoline() {
+        // Code running straight through would run into a trampoline anywhere in reach.
+        let source = format!("PLEASE, JUMP TO, THE END.\n{}THE END:\n{}", DOUBLE.repeat(20), POP);
+        let errors = compile_str(&source).unwrap_err();
+        assert_eq!(errors.iter().map(|_diagnostic| (_diagnostic.line, _diagnostic.kind)).collect::<Vec<_>>(), [(1, ErrorKind::LabelOutOfRange)]);
+
+        let errors = compile_str(&format!("PLEASE, JUMP TO, THE END.\nTHE END:\n{}", POP)).unwrap_err();
+        assert_eq!(errors.iter().map(|_diagnostic| _diagnostic.kind).collect::<Vec<_>>(), [ErrorKind::LabelTooClose]);
+    }
+
+    #[test]
+    fn disassembly_compiles_back_to_the_same_executable() {
+        use crate::disassembler::disassemble;
+
+        for _source in [std::fs::read_to_string("./test.sal").unwrap(), format!("PLEASE, JUMP TO, THE END.\n{}THE END:\n{}", blocks(7), POP)] {
+            let program = compile_str(&_source).unwrap();
+            let disassembly = disassemble(&program.bytes);
+            assert_eq!(compile_str(&disassembly).unwrap().bytes, program.bytes, "{}", disassembly);
+        }
+
+        let disassembly = disassemble(&[0b10111000, 0b01001001, 0b01111001]);
+        assert_eq!(disassembly, "PLEASE, JUMP TO, THE L2. 0000 10111000\nTHE L1:\nNOW, TO, THE FIRST REGISTRY, PUSH THE VALUE. 0001 01001001\nTHE L2:\nNOW, JUMP TO, THE L1. 0002 01111001\n");
     }
 }
@@ -0,0 +1,217 @@
+/***
+ * Instruction set of The Socially Acceptable Language
+ * - the one encoding shared by the compiler and the emulator
+ */
+
+#[cfg(test)]
+mod tests;
+
+/// Largest distance that fits in the two label bits of a branch.
+pub const BRANCH_DISTANCE_MAX: u8 = 0b11;
+/// Largest distance that fits in the three label bits of a jump.
+pub const JUMP_DISTANCE_MAX: u8 = 0b111;
+
+/// ## Prefix
+/// The first two bits are represented by the Prefix.
+///
+/// ### Groups
+/// Prefixes can further be divided into two groups:
+/// * Polite - "PLEASE" and "I'M BEGGING YOU"
+/// * Demanding - "NOW" and "I'M ORDERING YOU"
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Prefix {
+    /// "I'M ORDERING YOU", `00`
+    Ordering,
+    /// "NOW", `01`
+    Now,
+    /// "PLEASE", `10`
+    Please,
+    /// "I'M BEGGING YOU", `11`
+    Begging,
+}
+
+impl Prefix {
+    /// Polite prefixes jump forwards, demanding ones jump backwards.
+    pub fn is_polite(&self) -> bool {
+        matches!(self, Prefix::Please | Prefix::Begging)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Registry {
+    First,
+    Second,
+}
+
+/// The argument of `TO`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum StackAction {
+    Double,
+    Halve,
+    Push,
+    Pop,
+}
+
+/// Operation which uses three bits, and what it does with the three bits after it.
+///
+/// `distance` is what is encoded in the label bits, see `target` for where it leads.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Operation {
+    Increment { registry: Registry, negatively: bool, using_other_registry: bool },
+    To { registry: Registry, action: StackAction },
+    Access { registry: Registry, outputting: bool, as_character: bool },
+    Repeat { registry: Registry, ending: bool, second_loop: bool },
+    BranchIfGreater { registry: Registry, distance: u8 },
+    BranchIfZero { registry: Registry, distance: u8 },
+    JumpIfEqual { distance: u8 },
+    Jump { distance: u8 },
+}
+
+impl Operation {
+    /// The encoded distance, if the operation jumps.
+    pub fn distance(&self) -> Option<u8> {
+        match self {
+            Operation::BranchIfGreater { distance, .. }
+            | Operation::BranchIfZero { distance, .. }
+            | Operation::JumpIfEqual { distance }
+            | Operation::Jump { distance } => Some(*distance),
+            _ => None,
+        }
+    }
+
+    /// The largest distance the operation can encode, if it jumps.
+    pub fn distance_max(&self) -> Option<u8> {
+        match self {
+            Operation::BranchIfGreater { .. } | Operation::BranchIfZero { .. } => Some(BRANCH_DISTANCE_MAX),
+            Operation::JumpIfEqual { .. } | Operation::Jump { .. } => Some(JUMP_DISTANCE_MAX),
+            _ => None,
+        }
+    }
+}
+
+/// ## Instruction
+/// Every instruction is one byte.
+///
+/// ### Operation types
+/// | **Type** | **Encoding** |
+/// |:---------|:-------------|
+/// | One Argument | `Prefix<7:6>, Operation<5:3>, Registry<2>, Argument<1:0>` |
+/// | Two Arguments | `Prefix<7:6>, Operation<5:3>, Registry<2>, First Argument<1>, Second Argument<0>` |
+/// | Branch | `Prefix<7:6>, Operation<5:3>, Registry<2>, Label<1:0>` |
+/// | Jump | `Prefix<7:6>, Operation<5:3>, Label<2:0>` |
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Instruction {
+    pub prefix: Prefix,
+    pub operation: Operation,
+}
+
+/// Encode an instruction as its byte. `None` if its distance doesn't fit in its label bits.
+pub fn encode(instruction: &Instruction) -> Option<u8> {
+    let prefix: u8 = match instruction.prefix {
+        Prefix::Ordering => 0b00,
+        Prefix::Now => 0b01,
+        Prefix::Please => 0b10,
+        Prefix::Begging => 0b11,
+    };
+    let registry = |registry: Registry| match registry {
+        Registry::First => 0,
+        Registry::Second => 0b100,
+    };
+    let arguments = |first: bool, second: bool| ((first as u8) << 1) | second as u8;
+
+    let (operation, specifics): (u8, u8) = match instruction.operation {
+        Operation::Increment { registry: _registry, negatively, using_other_registry } => {
+            (0b000, registry(_registry) | arguments(negatively, using_other_registry))
+        }
+        Operation::To { registry: _registry, action } => {
+            let argument = match action {
+                StackAction::Double => arguments(false, false),
+                StackAction::Halve => arguments(true, false),
+                StackAction::Push => arguments(false, true),
+                StackAction::Pop => arguments(true, true),
+            };
+            (0b001, registry(_registry) | argument)
+        }
+        Operation::Access { registry: _registry, outputting, as_character } => {
+            (0b010, registry(_registry) | arguments(outputting, as_character))
+        }
+        Operation::Repeat { registry: _registry, ending, second_loop } => {
+            (0b011, registry(_registry) | arguments(ending, second_loop))
+        }
+        Operation::BranchIfGreater { registry: _registry, distance } if distance <= BRANCH_DISTANCE_MAX => {
+            (0b100, registry(_registry) | distance)
+        }
+        Operation::BranchIfZero { registry: _registry, distance } if distance <= BRANCH_DISTANCE_MAX => {
+            (0b101, registry(_registry) | distance)
+        }
+        Operation::JumpIfEqual { distance } if distance <= JUMP_DISTANCE_MAX => (0b110, distance),
+        Operation::Jump { distance } if distance <= JUMP_DISTANCE_MAX => (0b111, distance),
+        _ => return None,
+    };
+    Some((prefix << 6) | (operation << 3) | specifics)
+}
+
+/// Decode a byte. Every byte is some instruction.
+pub fn decode(byte: u8) -> Instruction {
+    let bit = |index: u32| byte & (1 << index) != 0;
+    let prefix = match byte >> 6 {
+        0b00 => Prefix::Ordering,
+        0b01 => Prefix::Now,
+        0b10 => Prefix::Please,
+        _ => Prefix::Begging,
+    };
+    let registry = if bit(2) { Registry::Second } else { Registry::First };
+
+    let operation = match (byte >> 3) & 0b111 {
+        0b000 => Operation::Increment { registry, negatively: bit(1), using_other_registry: bit(0) },
+        0b001 => {
+            let action = match (bit(1), bit(0)) {
+                (false, false) => StackAction::Double,
+                (true, false) => StackAction::Halve,
+                (false, true) => StackAction::Push,
+                (true, true) => StackAction::Pop,
+            };
+            Operation::To { registry, action }
+        }
+        0b010 => Operation::Access { registry, outputting: bit(1), as_character: bit(0) },
+        0b011 => Operation::Repeat { registry, ending: bit(1), second_loop: bit(0) },
+        0b100 => Operation::BranchIfGreater { registry, distance: byte & BRANCH_DISTANCE_MAX },
+        0b101 => Operation::BranchIfZero { registry, distance: byte & BRANCH_DISTANCE_MAX },
+        0b110 => Operation::JumpIfEqual { distance: byte & JUMP_DISTANCE_MAX },
+        _ => Operation::Jump { distance: byte & JUMP_DISTANCE_MAX },
+    };
+    Instruction { prefix, operation }
+}
+
+/// How many instructions a jump moves by, for an encoded `distance`.
+/// The emulator may double this before jumping.
+pub fn offset(distance: u8) -> usize {
+    distance as usize + 1
+}
+
+/// Where a jump moving by `offset` from `address` lands.
+///
+/// The emulator moves on by one instruction after every instruction,
+/// jumps included, so polite (forward) jumps land `offset + 1` ahead and
+/// demanding (backward) jumps land `offset - 1` behind.
+/// `None` if that is before the start of the program.
+pub fn target(prefix: Prefix, address: usize, offset: usize) -> Option<usize> {
+    if prefix.is_polite() {
+        Some(address + offset + 1)
+    } else {
+        (address + 1).checked_sub(offset)
+    }
+}
+
+/// The encoded distance of a jump from `address` to `target`, the inverse of `target`.
+/// `None` if no distance leads there with this prefix. The distance may still be
+/// too large for the operation, see `Operation::distance_max`.
+pub fn distance(prefix: Prefix, address: usize, target: usize) -> Option<usize> {
+    if prefix.is_polite() {
+        target.checked_sub(address + 2)
+    } else if target <= address {
+        Some(address - target)
+    } else {
+        None
+    }
+}
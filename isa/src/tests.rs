@@ -0,0 +1,84 @@
+use crate::*;
+
+// Every instruction that can be encoded, built up field by field rather than by decoding.
+fn every_instruction() -> Vec<Instruction> {
+    let prefixes = [Prefix::Ordering, Prefix::Now, Prefix::Please, Prefix::Begging];
+    let registries = [Registry::First, Registry::Second];
+    let actions = [StackAction::Double, StackAction::Halve, StackAction::Push, StackAction::Pop];
+    let flags = [(false, false), (false, true), (true, false), (true, true)];
+
+    let mut operations = Vec::new();
+    for _registry in registries {
+        for (_first, _second) in flags {
+            operations.push(Operation::Increment { registry: _registry, negatively: _first, using_other_registry: _second });
+            operations.push(Operation::Access { registry: _registry, outputting: _first, as_character: _second });
+            operations.push(Operation::Repeat { registry: _registry, ending: _first, second_loop: _second });
+        }
+        for _action in actions {
+            operations.push(Operation::To { registry: _registry, action: _action });
+        }
+        for _distance in 0..=BRANCH_DISTANCE_MAX {
+            operations.push(Operation::BranchIfGreater { registry: _registry, distance: _distance });
+            operations.push(Operation::BranchIfZero { registry: _registry, distance: _distance });
+        }
+    }
+    for _distance in 0..=JUMP_DISTANCE_MAX {
+        operations.push(Operation::JumpIfEqual { distance: _distance });
+        operations.push(Operation::Jump { distance: _distance });
+    }
+
+    let mut instructions = Vec::new();
+    for _prefix in prefixes {
+        for _operation in &operations {
+            instructions.push(Instruction { prefix: _prefix, operation: *_operation });
+        }
+    }
+    instructions
+}
+
+#[test]
+fn every_byte_round_trips() {
+    for _byte in 0..=u8::MAX {
+        assert_eq!(encode(&decode(_byte)), Some(_byte), "{:08b}", _byte);
+    }
+}
+
+#[test]
+fn every_instruction_round_trips() {
+    let instructions = every_instruction();
+    assert_eq!(instructions.len(), 256);
+    for _instruction in instructions {
+        let byte = encode(&_instruction).unwrap();
+        assert_eq!(decode(byte), _instruction);
+    }
+}
+
+#[test]
+fn distances_must_fit() {
+    let branch = Instruction { prefix: Prefix::Now, operation: Operation::BranchIfZero { registry: Registry::First, distance: 4 } };
+    assert_eq!(encode(&branch), None);
+    let jump = Instruction { prefix: Prefix::Now, operation: Operation::Jump { distance: 8 } };
+    assert_eq!(encode(&jump), None);
+}
+
+#[test]
+fn fields_are_where_the_table_says() {
+    let jump = Instruction { prefix: Prefix::Please, operation: Operation::Jump { distance: 0b101 } };
+    assert_eq!(encode(&jump), Some(0b10_111_101));
+    let pop = Instruction { prefix: Prefix::Now, operation: Operation::To { registry: Registry::Second, action: StackAction::Pop } };
+    assert_eq!(encode(&pop), Some(0b01_001_111));
+}
+
+#[test]
+fn distance_inverts_target() {
+    for _prefix in [Prefix::Ordering, Prefix::Now, Prefix::Please, Prefix::Begging] {
+        for _address in 0..20 {
+            for _distance in 0..=JUMP_DISTANCE_MAX {
+                if let Some(_target) = target(_prefix, _address, offset(_distance)) {
+                    assert_eq!(distance(_prefix, _address, _target), Some(_distance as usize));
+                }
+            }
+        }
+    }
+    assert_eq!(target(Prefix::Now, 1, offset(2)), None);
+}
@@ -0,0 +1,33 @@
+/***
+ * Emulator for The Socially Acceptable Language
+ * - how long the machine sulks for
+ */
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Where the machine's sulking goes.
+pub trait Clock: Debug {
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// Sulks for real, blocking the thread.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Only pretends to sulk, moving its own time on instead of blocking.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct VirtualClock {
+    pub now: Duration,
+}
+
+impl Clock for VirtualClock {
+    fn sleep(&mut self, duration: Duration) {
+        self.now += duration;
+    }
+}
@@ -0,0 +1,274 @@
+/***
+ * Emulator for The Socially Acceptable Language
+ * - stepping through a program, stopping where asked
+ */
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use crate::machine::{Machine, Outcome, Tolerance};
+
+const HELP: &str = "Commands:
+  step [<N>], s     Run the next instruction, or the next N.
+  continue, c       Run until a breakpoint, a watchpoint or the end of the program.
+  break <ADDRESS>, b <ADDRESS>
+                    Stop before running the instruction at ADDRESS.
+  break line <LINE> Stop before running the first instruction compiled from LINE,
+                    or from the first line after it that has any.
+  break, b          List breakpoints and watchpoints.
+  delete [<ADDRESS>], d
+                    Remove the breakpoint at ADDRESS, or every breakpoint.
+  watch <TOLERANCE>, w
+                    Stop when social credit goes past TOLERANCE, or back within it.
+                    Tolerances are small, medium and large, and small-close,
+                    medium-close and large-close, or all of them.
+  print, p          Print the registries, stacks, loops, social credit, irritation and mood.
+  help, h           Print this message.
+  quit, q           Stop debugging.";
+
+/// Why the debugger stopped running the program.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stop {
+    /// One instruction was run, or refused.
+    Stepped,
+    /// The machine is at a breakpoint.
+    Breakpoint(usize),
+    /// Social credit went from `from` to `to`, past `tolerance` or back within it.
+    Watchpoint { tolerance: Tolerance, from: i32, to: i32 },
+    /// The run is over.
+    Ended(Outcome),
+}
+
+/// ## Debugger
+/// Runs a machine an instruction at a time, stopping at breakpoints and when social
+/// credit crosses a watched tolerance.
+///
+/// Breakpoints by source line need the line of every instruction, from the debug info
+/// `salc --debug-info` writes next to the executable.
+#[derive(Debug)]
+pub struct Debugger {
+    pub machine: Machine,
+    /// The source line of every instruction, if there is debug info.
+    lines: Option<Vec<usize>>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<Tolerance>,
+    /// How the run ended, once it has.
+    pub outcome: Option<Outcome>,
+}
+
+impl Debugger {
+    pub fn new(machine: Machine, lines: Option<Vec<usize>>) -> Debugger {
+        Debugger { machine, lines, breakpoints: BTreeSet::new(), watchpoints: BTreeSet::new(), outcome: None }
+    }
+
+    pub fn break_at(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Break at the first instruction compiled from `line`, or from the first line after
+    /// it with any instructions. The address broken at.
+    pub fn break_at_line(&mut self, line: usize) -> Result<usize, String> {
+        let lines = self.lines.as_ref().ok_or("there is no debug info, break at an address instead")?;
+        let address = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, _line)| **_line >= line)
+            .min_by_key(|(_address, _line)| (**_line, *_address))
+            .map(|(_address, _)| _address)
+            .ok_or(format!("there are no instructions from line {} on", line))?;
+        self.break_at(address);
+        Ok(address)
+    }
+
+    /// Remove the breakpoint at `address`, `false` if there was none.
+    pub fn delete(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn watch(&mut self, tolerance: Tolerance) {
+        self.watchpoints.insert(tolerance);
+    }
+
+    /// The source line the instruction at `address` was compiled from, if there is debug info.
+    pub fn line(&self, address: usize) -> Option<usize> {
+        self.lines.as_ref().and_then(|_lines| _lines.get(address)).copied()
+    }
+
+    /// Run one instruction.
+    pub fn step(&mut self) -> Stop {
+        if let Some(_outcome) = self.outcome {
+            return Stop::Ended(_outcome);
+        }
+        let from = self.machine.social_credit;
+        if let Some(_outcome) = self.machine.step() {
+            self.outcome = Some(_outcome);
+            return Stop::Ended(_outcome);
+        }
+        let to = self.machine.social_credit;
+        let temperament = self.machine.temperament;
+        match self.watchpoints.iter().find(|_tolerance| _tolerance.is_past(&temperament, from) != _tolerance.is_past(&temperament, to)) {
+            Some(_tolerance) => Stop::Watchpoint { tolerance: *_tolerance, from, to },
+            None => Stop::Stepped,
+        }
+    }
+
+    /// Run until stopped by anything but a single step. A breakpoint only stops the machine
+    /// when it gets there, not when it is retrying the instruction it is at.
+    pub fn resume(&mut self) -> Stop {
+        loop {
+            let at = (self.machine.pc, self.machine.steps);
+            match self.step() {
+                Stop::Stepped if (self.machine.pc, self.machine.steps) != at && self.breakpoints.contains(&self.machine.pc) => {
+                    return Stop::Breakpoint(self.machine.pc)
+                }
+                Stop::Stepped => (),
+                _stop => return _stop,
+            }
+        }
+    }
+
+    /// Where the machine is, and the instruction it runs next.
+    pub fn location(&self) -> String {
+        let pc = self.machine.pc;
+        let line = self.line(pc).map(|_line| format!(" (line {})", _line)).unwrap_or_default();
+        match self.machine.program().get(pc) {
+            Some(_instruction) => format!("address {}{}: {:?} {:?}", pc, line, _instruction.prefix, _instruction.operation),
+            None => format!("address {}, past the end of the program", pc),
+        }
+    }
+
+    /// Everything the program can see, and how the machine feels about it.
+    pub fn state(&self) -> String {
+        let machine = &self.machine;
+        let loop_state = |start: usize, counter: i32, times: i32| format!("from address {}, {} of {} repeats", start, counter, times);
+        format!(
+            "{}\n\
+             polite registries: {} {}\n\
+             demanding registries: {} {}\n\
+             first stack: {:?}\n\
+             second stack: {:?}\n\
+             first loop: {}\n\
+             second loop: {}\n\
+             social credit {}, irritation {}, mood {:?}\n\
+             {} instruction(s), {} refusal(s)",
+            self.location(),
+            machine.polite_registries.0,
+            machine.polite_registries.1,
+            machine.demanding_registries.0,
+            machine.demanding_registries.1,
+            machine.stacks.0,
+            machine.stacks.1,
+            loop_state(machine.loops.0, machine.loop_counters.0, machine.loop_registries.0),
+            loop_state(machine.loops.1, machine.loop_counters.1, machine.loop_registries.1),
+            machine.social_credit,
+            machine.irritation,
+            machine.mood,
+            machine.steps,
+            machine.refusals,
+        )
+    }
+
+    /// Take commands from `commands` until told to quit or they run out, writing to `output`.
+    pub fn session<R, W>(&mut self, commands: R, mut output: W) -> io::Result<()>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        writeln!(output, "{}", self.location())?;
+        write!(output, "(sal) ")?;
+        output.flush()?;
+        for _command in commands.lines() {
+            let command = _command?;
+            let words: Vec<&str> = command.split_whitespace().collect();
+            match words.as_slice() {
+                ["quit"] | ["q"] => return Ok(()),
+                _ => writeln!(output, "{}", self.command(&words))?,
+            }
+            write!(output, "(sal) ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    // Run one command, returning what to print.
+    fn command(&mut self, words: &[&str]) -> String {
+        let address = |word: &str| word.parse::<usize>().map_err(|_| format!("expected an address, not `{}`", word));
+        let result = match words {
+            [] => Ok(String::new()),
+            ["step"] | ["s"] => {
+                let stop = self.step();
+                Ok(self.report(stop))
+            }
+            ["step", _count] | ["s", _count] => match _count.parse::<usize>() {
+                Ok(_count) => {
+                    let mut stop = Stop::Stepped;
+                    for _ in 0.._count {
+                        stop = self.step();
+                        if stop != Stop::Stepped {
+                            break;
+                        }
+                    }
+                    Ok(self.report(stop))
+                }
+                Err(_) => Err(format!("expected a number of instructions, not `{}`", _count)),
+            },
+            ["continue"] | ["c"] => {
+                let stop = self.resume();
+                Ok(self.report(stop))
+            }
+            ["break"] | ["b"] => {
+                let breakpoints: Vec<String> = self.breakpoints.iter().map(|_address| _address.to_string()).collect();
+                let watchpoints: Vec<String> = self.watchpoints.iter().map(|_tolerance| _tolerance.to_string()).collect();
+                Ok(format!("breakpoints: {}\nwatching: {}", breakpoints.join(", "), watchpoints.join(", ")))
+            }
+            ["break", "line", _line] | ["b", "line", _line] => _line
+                .parse::<usize>()
+                .map_err(|_| format!("expected a line, not `{}`", _line))
+                .and_then(|_line| self.break_at_line(_line))
+                .map(|_address| format!("Breakpoint at address {}", _address)),
+            ["break", _address] | ["b", _address] => address(_address).map(|_address| {
+                self.break_at(_address);
+                format!("Breakpoint at address {}", _address)
+            }),
+            ["delete"] | ["d"] => {
+                self.breakpoints.clear();
+                Ok("Deleted every breakpoint".to_string())
+            }
+            ["delete", _address] | ["d", _address] => address(_address).and_then(|_address| match self.delete(_address) {
+                true => Ok(format!("Deleted the breakpoint at address {}", _address)),
+                false => Err(format!("there is no breakpoint at address {}", _address)),
+            }),
+            ["watch", "all"] | ["w", "all"] => {
+                Tolerance::ALL.iter().for_each(|_tolerance| self.watch(*_tolerance));
+                Ok("Watching every tolerance".to_string())
+            }
+            ["watch", _tolerance] | ["w", _tolerance] => _tolerance.parse::<Tolerance>().map(|_tolerance| {
+                self.watch(_tolerance);
+                format!("Watching the {} tolerance, {}", _tolerance, _tolerance.of(&self.machine.temperament))
+            }),
+            ["print"] | ["p"] => Ok(self.state()),
+            ["help"] | ["h"] => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command `{}`, try help", words.join(" "))),
+        };
+        result.unwrap_or_else(|_message| format!("error: {}", _message))
+    }
+
+    // What the machine said on the way, what stopped it, and where it is now.
+    fn report(&mut self, stop: Stop) -> String {
+        let remarks: String = self.machine.take_remarks().iter().map(|_remark| format!("{}\n", _remark)).collect();
+        let stopped = match stop {
+            Stop::Stepped => self.location(),
+            Stop::Breakpoint(_) => format!("Breakpoint at {}", self.location()),
+            Stop::Watchpoint { tolerance, from, to } => format!(
+                "Social credit went from {} to {}, {} the {} tolerance of {}\n{}",
+                from,
+                to,
+                if tolerance.is_past(&self.machine.temperament, to) { "past" } else { "back within" },
+                tolerance,
+                tolerance.of(&self.machine.temperament),
+                self.location(),
+            ),
+            Stop::Ended(_outcome) => format!("{} at address {}: {}.", _outcome, self.machine.pc, _outcome.description()),
+        };
+        remarks + &stopped
+    }
+}
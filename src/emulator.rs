@@ -1,611 +1,429 @@
-use std::fs;
-use rand::Rng;
-use rand::rngs;
-use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
+/***
+ * Emulator for The Socially Acceptable Language
+ * - command line front end
+ */
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
 use chrono::prelude::*;
-
-#[derive(Clone, PartialEq, Debug, Copy)]
-enum Mood {
-    Bored, 
-    Happy, 
-    Sick, 
-    Maniacal, 
-    Angry, 
-    Annoyed, 
-    Lovestruck, 
-    Confused, 
+use the_socially_acceptable_language_compiler::debug_info;
+
+use crate::debugger::Debugger;
+use crate::io::{BoxedIo, CharPolicy, EofPolicy, Streams};
+use crate::machine::{Config, Machine, Mood, Outcome, RefusalPolicy};
+use crate::mood;
+use crate::timeline::Timeline;
+use crate::trace::Trace;
+
+// Exit statuses returned by `emulate`.
+// A run that doesn't halt exits with `Outcome::exit_code`.
+pub const SUCCESS: i32 = 0;
+pub const IO_ERROR: i32 = 2;
+pub const USAGE_ERROR: i32 = 64;
+
+const USAGE: &str = "Usage: murnion-task-9 [OPTIONS] <COMMAND>
+
+Runs executables compiled by salc, in whatever mood the emulator is in.
+
+Commands:
+  run <FILE>                     Run an executable.
+  debug <FILE>                   Run an executable under the debugger, taking debugger
+                                 commands from stdin, see `help` once it's started.
+                                 Breakpoints by source line need the FILE.saldbg
+                                 salc --debug-info writes. The program only gets
+                                 input with --input.
+  mood                           Print the mood the emulator is in.
+  mood forecast <FROM> [<TO>]    Print the mood of every hour from FROM up to TO,
+                                 or for a day if TO is left out.
+  mood next <MOOD> [<AFTER>]     Print the next hour the emulator is in MOOD,
+                                 starting with the current hour or AFTER.
+
+Times are RFC 3339, like 2021-12-24T18:00:00Z, or dates like 2021-12-24.
+Moods are Bored, Happy, Sick, Maniacal, Angry, Annoyed, Lovestruck and Confused.
+
+Options:
+  --mood <MOOD>    Be in MOOD instead of picking one.
+  --seed <SEED>    Pick the mood with SEED instead of the current hour.
+  --at <TIME>      Pick the mood the emulator would be in at TIME, not with --seed.
+  --no-sulk        Don't wait while the emulator sulks, only count how long it would have.
+  --input <FILE>   Read input from FILE instead of stdin.
+  --output <FILE>  Write output to FILE instead of stdout.
+  --eof <POLICY>   What reading past the end of input does: error (the default),
+                   refuse, or a number to read instead.
+  --invalid-char <POLICY>
+                   What writing a value that isn't a Unicode character does:
+                   replace it with U+FFFD (the default), skip it, or error.
+  --refusal <POLICY>
+                   What refusing a polite instruction does: abort (the default),
+                   skip it, or retry[:<ATTEMPTS>[:<MILLISECONDS>]], sulking twice
+                   as long before every try, 3 tries from 500 ms unless told otherwise.
+  --max-steps <STEPS>
+                   Stop after running STEPS instructions.
+  --time-limit <SECONDS>
+                   Stop after running for SECONDS, sulking included, even with
+                   --no-sulk. Sulking is cut short at the limit.
+  --trace <FILE>   Write every instruction run to FILE as JSON Lines, one object per
+                   instruction with its address, prefix, operation and its fields, the
+                   registries, social credit and irritation before and after it, stack
+                   depths, loops, whether it was refused or doubled, and any sulking.
+  --timeline-csv <FILE>
+                   Write social credit, irritation and whether the last instruction was
+                   polite, after every instruction, to FILE as CSV, along with the tolerances.
+  --timeline-svg <FILE>
+                   Draw the same as a chart, against the tolerances, to FILE as SVG.
+                   Both keep every point in memory until the run ends, about 32 bytes
+                   per instruction, so use --max-steps with programs that run long.
+  --detect-loops   Stop as soon as the emulator is back in a state it was in before,
+                   without having read any input since, as it would never get out.
+  -h, --help       Print this message.
+  -V, --version    Print the version.
+
+Exit status:
+  0   the program ran to its end
+  2   the executable, input or output could not be opened
+  10  an instruction was refused, a polite one past the small tolerance
+      or reading past the end of input with --eof refuse
+  11  the emulator got fed up, irritation reached 1000
+  12  the emulator walked out, begging past the large tolerance
+  13  the emulator rage quit, ordering past the medium tolerance
+  14  the program's input could not be read or its output could not be written
+  15  the program jumped to before its start or past its end
+  16  the program ran --max-steps instructions
+  17  the program ran for --time-limit seconds
+  18  the program got stuck in a loop, with --detect-loops
+  64  the command line was not understood";
+
+#[derive(Clone, PartialEq, Debug)]
+enum Command {
+    Run(String),
+    Debug(String),
+    Mood,
+    Forecast(DateTime<Utc>, Option<DateTime<Utc>>),
+    Next(Mood, Option<DateTime<Utc>>),
+    Help,
+    Version,
 }
 
-#[derive(Clone, PartialEq, Debug, Copy)]
-enum PrefixType {
-    POLITE_STRONG,
-    POLITE,
-    DEMANDING,
-    DEMANDING_STRONG,
+#[derive(Clone, PartialEq, Debug)]
+struct Options {
+    command: Command,
+    mood: Option<Mood>,
+    seed: Option<u64>,
+    no_sulk: bool,
+    input: Option<String>,
+    output: Option<String>,
+    eof: EofPolicy,
+    invalid_char: CharPolicy,
+    refusal: RefusalPolicy,
+    max_steps: Option<u64>,
+    time_limit: Option<Duration>,
+    detect_loops: bool,
+    trace: Option<String>,
+    timeline_csv: Option<String>,
+    timeline_svg: Option<String>,
 }
 
-impl PrefixType {
-    fn find(code: [bool; 2]) -> PrefixType {
-        match code {
-            [false, false] => PrefixType::DEMANDING_STRONG,
-            [false, true] => PrefixType::DEMANDING,
-            [true, false] => PrefixType::POLITE,
-            [true, true] => PrefixType::POLITE_STRONG,
+impl Options {
+    // The mood to run in, along with the seed it was picked with unless it was forced.
+    fn config(&self) -> Config {
+        let mut config = Config {
+            virtual_time: self.no_sulk,
+            eof: self.eof,
+            invalid_char: self.invalid_char,
+            refusal: self.refusal,
+            max_steps: self.max_steps,
+            time_limit: self.time_limit,
+            detect_loops: self.detect_loops,
+            ..Config::default()
+        };
+        match self.mood {
+            Some(_mood) => config.mood = _mood,
+            None => config.seed = Some(self.seed.unwrap_or_else(|| mood::seed_at(Utc::now()))),
         }
+        config
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Copy)]
-enum OperationType {
-    INCREMENT, 
-    TO,
-    ACCESS,
-    LOOP,
-    BRANCH_IF_GREATER,
-    BRANCH_IF_ZERO,
-    BRANCH_IF_EQUAL,
-    JUMP,
-}
-
-impl OperationType {
-    fn find(code: [bool; 3]) -> OperationType {
-        match code {
-            [false, false, false] => OperationType::INCREMENT,
-            [false, false, true] => OperationType::TO,
-            [false, true, false] => OperationType::ACCESS,
-            [false, true, true] => OperationType::LOOP,
-            [true, false, false] => OperationType::BRANCH_IF_GREATER,
-            [true, false, true] => OperationType::BRANCH_IF_ZERO,
-            [true, true, false] => OperationType::BRANCH_IF_EQUAL,
-            [true, true, true] => OperationType::JUMP,
-            _ => panic!()
+/// Front end of the emulator. Takes the command line arguments, without the program name,
+/// and returns the exit status.
+pub fn emulate(arguments: Vec<String>) -> i32 {
+    let options = match parse_arguments(arguments) {
+        Ok(_options) => _options,
+        Err(_message) => {
+            eprintln!("error: {}\n\n{}", _message, USAGE);
+            return USAGE_ERROR;
+        }
+    };
+
+    let format = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+    match options.command {
+        Command::Help => println!("{}", USAGE),
+        Command::Version => println!("murnion-task-9 {}", env!("CARGO_PKG_VERSION")),
+        Command::Mood => {
+            let config = options.config();
+            match config.seed {
+                Some(_seed) => println!("{:?} (seed {})", mood::mood_of(_seed), _seed),
+                None => println!("{:?}", config.mood),
+            }
         }
+        Command::Forecast(_from, _to) => {
+            for (_hour, _mood) in mood::forecast(_from, _to.unwrap_or(_from + chrono::Duration::days(1))) {
+                println!("{} {:?}", format(_hour), _mood);
+            }
+        }
+        Command::Next(_mood, _after) => match mood::next(_mood, _after.unwrap_or_else(Utc::now)) {
+            Some(_hour) => println!("{} {:?}", format(_hour), _mood),
+            None => println!("Never {:?}", _mood),
+        },
+        Command::Run(ref _file) => return run(_file, &options),
+        Command::Debug(ref _file) => return debug(_file, &options),
     }
+    SUCCESS
 }
 
-// Generic helper function to convert compiler-fed code byte by byte into actual executable code.
-fn convert_to_instruction(_byte: u8) -> (PrefixType, OperationType, [bool; 3]) {
-    let mut byte = _byte;
-    let base: u8 = 2;
-    let mut bool_array: [bool; 8] = [false; 8];
-    for i in 0..8 {
-        if byte >= base.pow(7 - i) {
-            byte -= base.pow(7 - i);
-            bool_array[i as usize] = true;
-        }
+fn run(file: &str, options: &Options) -> i32 {
+    let mut machine = match machine(file, options) {
+        Ok(_machine) => _machine,
+        Err(_status) => return _status,
+    };
+    let outcome = machine.run_with_remarks(|_remark| eprintln!("{}", _remark));
+    summarize(&machine, outcome);
+    match export_timeline(&machine, options) {
+        Ok(()) => outcome.exit_code(),
+        Err(_status) => _status,
     }
-
-    let prefix_type = PrefixType::find([bool_array[0], bool_array[1]]);
-    let operation_type = OperationType::find([bool_array[2], bool_array[3], bool_array[4]]);
-    let specifics = [bool_array[5], bool_array[6], bool_array[7]];
-
-    (prefix_type, operation_type, specifics)
 }
 
-// Rng that only changes every hour.
-fn generate_rng() -> rngs::StdRng {
-    let time = chrono::offset::Utc::now();
-    let hours = time.time().hour();
-    let days = time.date().ordinal();
-    let seed = hours * days;
-    let mut rng: rngs::StdRng = rand::SeedableRng::seed_from_u64(seed as u64);
-    rng
+fn debug(file: &str, options: &Options) -> i32 {
+    let machine = match machine(file, options) {
+        Ok(_machine) => _machine,
+        Err(_status) => return _status,
+    };
+    // Debug info is optional, there just are no breakpoints by line without it.
+    let debug_info = Path::new(file).with_extension(debug_info::DEBUG_INFO_EXTENSION);
+    let lines = match fs::read_to_string(&debug_info) {
+        Ok(_text) => match debug_info::read_debug_info(&_text) {
+            Some(_lines) if _lines.len() == machine.program().len() => Some(_lines),
+            _ => {
+                eprintln!("Ignoring {:?}, it isn't the debug info of this executable.", debug_info);
+                None
+            }
+        },
+        Err(_) => None,
+    };
+    let mut debugger = Debugger::new(machine, lines);
+    if let Err(_error) = debugger.session(std::io::stdin().lock(), std::io::stdout()) {
+        eprintln!("Failed to debug: {}", _error);
+        return IO_ERROR;
+    }
+    if let Some(_outcome) = debugger.outcome {
+        summarize(&debugger.machine, _outcome);
+    }
+    if let Err(_status) = export_timeline(&debugger.machine, options) {
+        return _status;
+    }
+    debugger.outcome.map_or(SUCCESS, |_outcome| _outcome.exit_code())
 }
 
-// Functionally just the "main" code, wrapped in a library.
-pub fn emulate() {
-    // Base values to get the emulator started.
-    let mut irritation: i32 = 0;
-    let mut last_was_positive = true;
-    let mut social_credit: i32 = 0;
-    let mut mood = Mood::Bored;
-    let mut SMALL_TOLERANCE: i32 = 50;
-    let mut SMALL_TOLERANCE_CLOSE: i32 = 25;
-    let mut MEDIUM_TOLERANCE: i32 = 75;
-    let mut MEDIUM_TOLERANCE_CLOSE: i32 = 50;
-    let mut LARGE_TOLERANCE: i32 = 100;
-    let mut LARGE_TOLERANCE_CLOSE: i32 = 75;
-
-    // The speed at which different prefixes change your social credit and irritation.
-    let mut polite_social_change: i32 = 2;
-    let mut polite_strong_social_change: i32 = -5;
-    let mut demanding_social_change: i32 = -2;
-    let mut demanding_strong_social_change: i32 = -5;
-    let mut irritation_change: i32 = 4;
-    let mut irritation_decay: i32 = -1;
-
-    // The technical assignments. Meant to emulate bespoke Assembly code for a custom-made chipset.
-    let mut polite_registries: (i32, i32) = (0, 0);
-    let mut demanding_registries: (i32, i32) = (0, 0);
-    let mut stacks = (Vec::<i32>::new(), Vec::<i32>::new());
-    let mut loops: (i32, i32) = (0, 0);
-    let mut loop_counters: (i32, i32) = (0, 0);
-    let mut loop_registries: (i32, i32) = (0, 0);
-    let mut forcedmood: Option<u64> = None;
-
-    // A lot of stuff for forcing the mood of the emulator to conform.
-    // Almost necessary to be able to demonstrate the capabilities of the language.
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() == 3 {
-        match args[2].clone().as_str() {
-            "--Bored" => forcedmood = Some(0),
-            "--Happy" => forcedmood = Some(1),
-            "--Sick" => forcedmood = Some(2),
-            "--Maniacal" => forcedmood = Some(3),
-            "--Angry" => forcedmood = Some(4),
-            "--Annoyed" => forcedmood = Some(5),
-            "--Lovestruck" => forcedmood = Some(6),
-            "--Confused" => forcedmood = Some(7),
-            _ => ()
+// The machine running `file`, or the exit status if it can't be started.
+fn machine(file: &str, options: &Options) -> Result<Machine, i32> {
+    let executable = match fs::read(file) {
+        Ok(_executable) => _executable,
+        Err(_error) => {
+            eprintln!("Failed to read file {:?}: {}", file, _error);
+            return Err(IO_ERROR);
         }
+    };
+    let streams = match streams(options) {
+        Ok(_streams) => _streams,
+        Err((_file, _error)) => {
+            eprintln!("Failed to open file {:?}: {}", _file, _error);
+            return Err(IO_ERROR);
+        }
+    };
+    let config = options.config();
+    let mut machine = Machine::new(&executable, config.clone()).with_io(Box::new(streams));
+    if options.timeline_csv.is_some() || options.timeline_svg.is_some() {
+        machine = machine.with_timeline();
     }
-
-    match if forcedmood.is_some() {forcedmood.unwrap()} else { generate_rng().gen::<u64>() % 8 } {
-        0 => {
-            mood = Mood::Bored;
-        },
-        1 => {
-            mood = Mood::Happy;
-            social_credit = 25;
-            SMALL_TOLERANCE = 75;
-            MEDIUM_TOLERANCE = 100;
-            LARGE_TOLERANCE = 125;
-            SMALL_TOLERANCE_CLOSE = 50;
-            MEDIUM_TOLERANCE_CLOSE = 75;
-            LARGE_TOLERANCE_CLOSE = 100;
-        },
-        2 => {
-            mood = Mood::Sick;
-            social_credit = -25;
-            polite_strong_social_change = -7;
-            demanding_social_change = -4;
-            irritation_decay = 0;
-        },
-        3 => {
-            mood = Mood::Maniacal;
-            polite_social_change = 0;
-            MEDIUM_TOLERANCE_CLOSE = 0;
-        },
-        4 => {
-            mood = Mood::Angry;
-            demanding_social_change = -4;
-            demanding_strong_social_change = -8;
-            polite_strong_social_change = -4;
-            LARGE_TOLERANCE = 75;
-            LARGE_TOLERANCE_CLOSE = 50;
-        },
-        5 => {
-            mood = Mood::Annoyed;
-            irritation_change = 8;
-        },
-        6 => {
-            mood = Mood::Lovestruck;
-            polite_social_change = 5;
-            polite_strong_social_change = -2;
-            social_credit = 25;
-            SMALL_TOLERANCE = 100;
-            MEDIUM_TOLERANCE = 125;
-            LARGE_TOLERANCE = 150;
-            SMALL_TOLERANCE_CLOSE = 50;
-            MEDIUM_TOLERANCE_CLOSE = 75;
-            LARGE_TOLERANCE_CLOSE = 100;
-            irritation_change = 1;
-        },
-        7 => {
-            mood = Mood::Confused;
-            polite_social_change = -2;
-            polite_strong_social_change = -4;
-            demanding_social_change = 2;
-            demanding_strong_social_change = 5;
-        },
-        _ => panic!("Generated a number outside of the range, for moods!")
+    if let Some(_file) = &options.trace {
+        match File::create(_file) {
+            Ok(_trace) => machine = machine.with_trace(Trace::new(Box::new(BufWriter::new(_trace)))),
+            Err(_error) => {
+                eprintln!("Failed to open file {:?}: {}", _file, _error);
+                return Err(IO_ERROR);
+            }
+        }
     }
+    match config.seed {
+        Some(_seed) => eprintln!("Mood: {:?} (seed {})", machine.mood, _seed),
+        None => eprintln!("Mood: {:?} (forced)", machine.mood),
+    }
+    Ok(machine)
+}
 
-    // --get_mood option lets you see the mood of the emulator.
-    if args.len() == 2 {
-        if args[1].clone() == "get_mood" {
-            println!("{:?}", mood);
-            panic!("Got mood.");
+// Write the timeline to the files given with --timeline-csv and --timeline-svg.
+fn export_timeline(machine: &Machine, options: &Options) -> Result<(), i32> {
+    let timeline = match machine.timeline() {
+        Some(_timeline) => _timeline,
+        None => return Ok(()),
+    };
+    let exports = [(&options.timeline_csv, Timeline::csv as fn(&Timeline) -> String), (&options.timeline_svg, Timeline::svg)];
+    for (_file, _export) in exports {
+        if let Some(_file) = _file {
+            if let Err(_error) = fs::write(_file, _export(timeline)) {
+                eprintln!("Failed to write {:?}: {}", _file, _error);
+                return Err(IO_ERROR);
+            }
         }
     }
-    if args.len() < 2 {
-        println!("[ERROR] A path to a SAL file must be provided!");
-    } else {
-        // Convert the read file (a byte list) into a list of operations.
-        let commands: Vec<(PrefixType, OperationType, [bool; 3])> = fs::read(args[1].clone()).ok().unwrap()
-                                                                                        .into_iter()
-                                                                                        .map(|x| convert_to_instruction(x))
-                                                                                        .collect();
+    Ok(())
+}
+
+// How the run ended, on stderr.
+fn summarize(machine: &Machine, outcome: Outcome) {
+    eprintln!(
+        "{} after {} instruction(s), {} refusal(s) and {:?} of sulking, at address {} with social credit {} and irritation {}: {}.",
+        outcome,
+        machine.steps,
+        machine.refusals,
+        machine.sulked,
+        machine.pc,
+        machine.social_credit,
+        machine.irritation,
+        outcome.description(),
+    );
+    if let Some(_error) = &machine.io_error {
+        eprintln!("error: {}", _error);
+    }
+    if let Outcome::JumpOutOfBounds { address, offset, prefix, doubled } = outcome {
+        eprintln!(
+            "error: the {:?} jump at address {} moved by {}{}, out of a program of {} instruction(s)",
+            prefix,
+            address,
+            offset,
+            if doubled { " after doubling" } else { "" },
+            machine.program().len(),
+        );
+    }
+}
 
-        // Then loop through the commands.
-        let mut i = 0;
-        while i < commands.len() {
-            let prefix_type = commands[i].0.clone();
-            let operation_type = commands[i].1.clone();
-            let specifics = commands[i].2;
+// The files given with --input and --output, or stdin and stdout.
+// The debugger takes its commands from stdin, so a program being debugged has no input without --input.
+fn streams(options: &Options) -> Result<BoxedIo, (String, std::io::Error)> {
+    let input: Box<dyn BufRead> = match &options.input {
+        Some(_file) => Box::new(BufReader::new(File::open(_file).map_err(|_error| (_file.clone(), _error))?)),
+        None if matches!(options.command, Command::Debug(_)) => Box::new(std::io::empty()),
+        None => Box::new(BufReader::new(std::io::stdin())),
+    };
+    let output: Box<dyn Write> = match &options.output {
+        Some(_file) => Box::new(File::create(_file).map_err(|_error| (_file.clone(), _error))?),
+        None => Box::new(std::io::stdout()),
+    };
+    Ok(Streams::new(input, output))
+}
 
-            // Here we first check various social values, to determine if the operation is changed.
-            if irritation >= 1000 {
-                eprintln!("This program is DONE with your wishy-washy attitude.");
-                std::thread::sleep(std::time::Duration::from_millis(500));
-                panic!();
+fn parse_arguments(arguments: Vec<String>) -> Result<Options, String> {
+    // The command is only known once every flag is out of the way.
+    let mut options = Options {
+        command: Command::Help,
+        mood: None,
+        seed: None,
+        no_sulk: false,
+        input: None,
+        output: None,
+        eof: EofPolicy::default(),
+        invalid_char: CharPolicy::default(),
+        refusal: RefusalPolicy::default(),
+        max_steps: None,
+        time_limit: None,
+        detect_loops: false,
+        trace: None,
+        timeline_csv: None,
+        timeline_svg: None,
+    };
+    let mut positionals: Vec<String> = Vec::new();
+    // Both pick the seed, so only one of them can be used.
+    let (mut seeded, mut at) = (false, false);
+
+    let mut arguments = arguments.into_iter();
+    while let Some(_argument) = arguments.next() {
+        let mut value = |flag: &str| arguments.next().ok_or(format!("{} needs a value", flag));
+        match _argument.as_str() {
+            "-h" | "--help" => return Ok(options),
+            "-V" | "--version" => return Ok(Options { command: Command::Version, ..options }),
+            "--mood" => options.mood = Some(value("--mood")?.parse()?),
+            "--seed" => {
+                let _seed = value("--seed")?;
+                options.seed = Some(_seed.parse().map_err(|_| format!("--seed needs a whole number, not `{}`", _seed))?);
+                seeded = true;
             }
-
-            match prefix_type {
-                PrefixType::POLITE => {
-                    if social_credit >= SMALL_TOLERANCE || social_credit <= SMALL_TOLERANCE*-1 {
-                        continue;
-                    }
-                    if social_credit >= SMALL_TOLERANCE_CLOSE || social_credit <= SMALL_TOLERANCE_CLOSE*-1 {
-                        eprintln!("\"I guess...\"");
-                    }
-                    if !last_was_positive {
-                        irritation += irritation_change;
-                        last_was_positive = true;
-                    } else if irritation > 0 {
-                        irritation += irritation_decay;
-                    }
-                    social_credit += polite_social_change;
-                },
-                PrefixType::POLITE_STRONG => {
-                    if social_credit >= LARGE_TOLERANCE || social_credit <= LARGE_TOLERANCE*-1 {
-                        eprintln!("The program got tired of your snivelling attitude and left...");
-                        std::thread::sleep(std::time::Duration::from_millis(500));
-                        panic!();
-                    }
-                    if social_credit >= LARGE_TOLERANCE_CLOSE || social_credit <= LARGE_TOLERANCE_CLOSE*-1 {
-                        std::thread::sleep(std::time::Duration::from_millis(1500));
-                    }
-                    if last_was_positive {
-                        irritation += irritation_change;
-                        last_was_positive = false;
-                    } else if irritation > 0 {
-                        irritation += irritation_decay;
-                    }
-                    social_credit += polite_strong_social_change;
-                },
-                PrefixType::DEMANDING => {
-                    if social_credit >= MEDIUM_TOLERANCE || social_credit <= MEDIUM_TOLERANCE*-1 {
-                        eprintln!("\"Oh, now? Really, now?\"");
-                        eprintln!("The program is defiantly doing nothing.");
-                        std::thread::sleep(std::time::Duration::from_millis(15000));
-                        eprintln!("\"Fine.\"");
-                    }
-                    if social_credit >= MEDIUM_TOLERANCE_CLOSE || social_credit <= MEDIUM_TOLERANCE_CLOSE*-1 {
-                        std::thread::sleep(std::time::Duration::from_millis(500));
-                    }
-                    if last_was_positive {
-                        irritation += irritation_change;
-                        last_was_positive = false;
-                    } else if irritation > 0 {
-                        irritation += irritation_decay;
-                    }
-                    social_credit += demanding_social_change;
-                }
-                PrefixType::DEMANDING_STRONG => {
-                    if social_credit >= MEDIUM_TOLERANCE || social_credit <= MEDIUM_TOLERANCE*-1 {
-                        eprintln!("\"No, you know what, f*** you!\"");
-                        std::thread::sleep(std::time::Duration::from_millis(1000));
-                        panic!();
-                    }
-                    if last_was_positive {
-                        irritation += irritation_change;
-                        last_was_positive = false;
-                    } else if irritation > 0 {
-                        irritation += irritation_decay;
-                    }
-                    social_credit += demanding_strong_social_change;
-                }
-                _ => panic!()
+            "--at" => {
+                options.seed = Some(mood::seed_at(parse_time(&value("--at")?)?));
+                at = true;
             }
-
-            // A lot of these are very ugly. Badly made, too.
-            // Often, the operation will have to load the registry into a temporary value.
-            // Then modify that value, and set the registry to that temporary value.
-            match operation_type {
-                OperationType::INCREMENT => {
-                    let mut selected_registries: (i32, i32);
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        selected_registries = polite_registries;
-                    } else {
-                        selected_registries = demanding_registries;
-                    }
-
-                    let mut selected_registry = 0;
-                    let mut other_registry = 0;
-                    if specifics[0] {
-                        selected_registry = selected_registries.1;
-                        other_registry = selected_registries.0;
-                    } else {
-                        selected_registry = selected_registries.0;
-                        other_registry = selected_registries.1;
-                    }
-
-                    let mut value = 1;
-                    if specifics[2] {
-                        value = other_registry;
-                    }
-                    if prefix_type == PrefixType::DEMANDING_STRONG && (social_credit >= MEDIUM_TOLERANCE_CLOSE || social_credit <= -1*MEDIUM_TOLERANCE_CLOSE) {
-                        value *= 2;
-                    }
-
-                    if specifics[1] {
-                        selected_registry -= value;
-                    } else {
-                        selected_registry += value;
-                    }
-
-                    if specifics[0] {
-                        selected_registries.1 = selected_registry;
-                    } else {
-                        selected_registries.0 = selected_registry;
-                    }
-
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        polite_registries = selected_registries;
-                    } else {
-                        demanding_registries = selected_registries;
-                    }
-                },
-                OperationType::TO => {
-                    let mut selected_registries: (i32, i32);
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        selected_registries = polite_registries;
-                    } else {
-                        selected_registries = demanding_registries;
-                    }
-
-                    let mut selected_registry = 0;
-                    let mut selected_stack: &mut Vec<i32>;
-                    if specifics[0] {
-                        selected_registry = selected_registries.1;
-                        selected_stack = &mut stacks.1;
-                    } else {
-                        selected_registry = selected_registries.0;
-                        selected_stack = &mut stacks.0;
-                    }
-
-                    if specifics[1] {
-                        if specifics[2] {
-                            let mut temp = selected_stack.pop();
-                            if temp == None { temp = Some(0); }
-                            selected_registry = temp.unwrap();
-                        } else {
-                            selected_registry /= 2;
-                        }
-                    } else {
-                        if specifics[2] {
-                            selected_stack.push(selected_registry);
-                        } else {
-                            selected_registry *= 2;
-                        }
-                    }
-
-                    if specifics[0] {
-                        selected_registries.1 = selected_registry;
-                    } else {
-                        selected_registries.0 = selected_registry;
-                    }
-
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        polite_registries = selected_registries;
-                    } else {
-                        demanding_registries = selected_registries;
-                    }
-                }
-                OperationType::ACCESS => {
-                    let mut selected_registries: (i32, i32);
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        selected_registries = polite_registries;
-                    } else {
-                        selected_registries = demanding_registries;
-                    }
-
-                    let mut selected_registry = 0;
-                    if specifics[0] {
-                        selected_registry = selected_registries.1;
-                    } else {
-                        selected_registry = selected_registries.0;
-                    }
-
-                    if !specifics[1] {
-                        let mut input = String::new();
-                        if specifics[2] {
-                            let _input = std::io::stdin().read_line(&mut input);
-                            if (input.chars().nth(0)).unwrap().is_ascii() {
-                                selected_registry = ((input.chars().nth(0)).unwrap() as u8) as i32;
-                            }
-                        } else {
-                            let _input = std::io::stdin().read_line(&mut input);
-                            if (input.chars().nth(0)).unwrap().is_numeric() {
-                                let temp = input.split_at(input.len() - 1).0.parse::<i32>();
-                                if temp.is_ok() {
-                                    selected_registry = temp.unwrap();
-                                }
-                            }
-                        }
-                    } else {
-                        if specifics[2] {
-                            if selected_registry < 256 {
-                                println!("{:?}", (selected_registry as u8) as char)
-                            }
-                        } else {
-                            println!("{}", selected_registry);
-                        }
-                    }
-
-                    if specifics[0] {
-                        selected_registries.1 = selected_registry;
-                    } else {
-                        selected_registries.0 = selected_registry;
-                    }
-
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        polite_registries = selected_registries;
-                    } else {
-                        demanding_registries = selected_registries;
-                    }
-                },
-                OperationType::LOOP => {
-                    let selected_registries: (i32, i32);
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        selected_registries = polite_registries;
-                    } else {
-                        selected_registries = demanding_registries;
-                    }
-
-                    let selected_registry;
-                    if specifics[0] {
-                        selected_registry = selected_registries.1;
-                    } else {
-                        selected_registry = selected_registries.0;
-                    }
-
-                    if specifics[2] {
-                        if specifics[1] {
-                            if loop_counters.1 < loop_registries.1 {
-                                loop_counters.1 += 1;
-                                i = loops.1 as usize;
-                            }
-                        } else {
-                            loop_counters.1 = 0;
-                            loop_registries.1 = selected_registry;
-                            loops.1 = i as i32;
-                        }
-                    } else {
-                        if specifics[1] {
-                            if loop_counters.0 < loop_registries.0 {
-                                loop_counters.0 += 1;
-                                i = loops.0 as usize;
-                            }
-                        } else {
-                            loop_registries.0 = selected_registry;
-                            loops.0 = i as i32;
-                            loop_counters.0 = 0;
-                        }
-                    }
-                },
-                OperationType::BRANCH_IF_GREATER => {
-                    let mut selected_registries: (i32, i32);
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        selected_registries = polite_registries;
-                    } else {
-                        selected_registries = demanding_registries;
-                    }
-
-                    let mut selected_registry = 0;
-                    let mut other_registry = 0;
-                    if specifics[0] {
-                        selected_registry = selected_registries.1;
-                        other_registry = selected_registries.0;
-                    } else {
-                        selected_registry = selected_registries.0;
-                        other_registry = selected_registries.1;
-                    }
-
-                    if selected_registry > other_registry {
-                        let mut value = 1;
-                        if specifics[1] {
-                            value += 2;
-                        }
-                        if specifics[2] {
-                            value += 1;
-                        }
-                        if prefix_type == PrefixType::DEMANDING_STRONG && (social_credit >= MEDIUM_TOLERANCE_CLOSE || social_credit <= -1*MEDIUM_TOLERANCE_CLOSE) {
-                            value *= 2;
-                        }
-                        if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                            i += value;
-                        } else {
-                            i -= value;
-                        }
-                    }
-                }
-                OperationType::BRANCH_IF_ZERO => {
-                    let mut selected_registries: (i32, i32);
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        selected_registries = polite_registries;
-                    } else {
-                        selected_registries = demanding_registries;
-                    }
-
-                    let mut selected_registry = 0;
-                    if specifics[0] {
-                        selected_registry = selected_registries.1;
-                    } else {
-                        selected_registry = selected_registries.0;
-                    }
-
-                    if selected_registry == 0 {
-                        let mut value = 0;
-                        if specifics[1] {
-                            value += 2;
-                        }
-                        if specifics[2] {
-                            value += 1;
-                        }
-                        if prefix_type == PrefixType::DEMANDING_STRONG && (social_credit >= MEDIUM_TOLERANCE_CLOSE || social_credit <= -1*MEDIUM_TOLERANCE_CLOSE) {
-                            value *= 2;
-                        }
-                        if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                            i += value;
-                        } else {
-                            i -= value;
-                        }
-                    }
-                }
-                OperationType::BRANCH_IF_EQUAL => {
-                    let selected_registries: (i32, i32);
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        selected_registries = polite_registries;
-                    } else {
-                        selected_registries = demanding_registries;
-                    }
-
-                    if selected_registries.0 == selected_registries.1 {
-                        let mut value = 1;
-                        if specifics[0] {
-                            value += 4;
-                        }
-                        if specifics[1] {
-                            value += 2;
-                        }
-                        if specifics[2] {
-                            value += 1;
-                        }
-                        if prefix_type == PrefixType::DEMANDING_STRONG && (social_credit >= MEDIUM_TOLERANCE_CLOSE || social_credit <= -1*MEDIUM_TOLERANCE_CLOSE) {
-                            value *= 2;
-                        }
-                        if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                            i += value;
-                        } else {
-                            i -= value;
-                        }
-                    }
-                }
-                OperationType::JUMP => {
-                    let mut value = 1;
-                    if specifics[0] {
-                        value += 4;
-                    }
-                    if specifics[1] {
-                        value += 2;
-                    }
-                    if specifics[2] {
-                        value += 1;
-                    }
-                    if prefix_type == PrefixType::DEMANDING_STRONG && (social_credit >= MEDIUM_TOLERANCE_CLOSE || social_credit <= -1*MEDIUM_TOLERANCE_CLOSE) {
-                        value *= 2;
-                    }
-                    if prefix_type == PrefixType::POLITE || prefix_type == PrefixType::POLITE_STRONG {
-                        i += value;
-                    } else {
-                        i -= value;
-                    }
-                }
-                _ => panic!(),
+            "--no-sulk" => options.no_sulk = true,
+            "--input" => options.input = Some(value("--input")?),
+            "--output" => options.output = Some(value("--output")?),
+            "--eof" => options.eof = value("--eof")?.parse()?,
+            "--invalid-char" => options.invalid_char = value("--invalid-char")?.parse()?,
+            "--refusal" => options.refusal = value("--refusal")?.parse()?,
+            "--max-steps" => {
+                let _steps = value("--max-steps")?;
+                options.max_steps = Some(_steps.parse().map_err(|_| format!("--max-steps needs a whole number, not `{}`", _steps))?);
+            }
+            "--time-limit" => {
+                let _seconds = value("--time-limit")?;
+                let error = || format!("--time-limit needs a number of seconds, not `{}`", _seconds);
+                let seconds: f64 = _seconds.parse().map_err(|_| error())?;
+                options.time_limit = Some(Duration::try_from_secs_f64(seconds).map_err(|_| error())?);
             }
-            i += 1;
+            "--detect-loops" => options.detect_loops = true,
+            "--trace" => options.trace = Some(value("--trace")?),
+            "--timeline-csv" => options.timeline_csv = Some(value("--timeline-csv")?),
+            "--timeline-svg" => options.timeline_svg = Some(value("--timeline-svg")?),
+            _flag if _flag.starts_with('-') => return Err(format!("unknown option {}", _flag)),
+            _ => positionals.push(_argument),
+        }
+    }
+    if seeded && at {
+        return Err("--seed can't be used together with --at".to_string());
+    }
+    if options.mood.is_some() && options.seed.is_some() {
+        return Err("--mood can't be used together with --seed or --at".to_string());
+    }
+
+    let positional = |_index: usize| positionals.get(_index).map(|_value| _value.as_str());
+    let time = |_index: usize| positional(_index).map(parse_time).transpose();
+    options.command = match (positional(0), positional(1)) {
+        (Some("run"), Some(_file)) if positionals.len() == 2 => Command::Run(_file.to_string()),
+        (Some("run"), None) => return Err("a path to a SAL executable must be provided".to_string()),
+        (Some("debug"), Some(_file)) if positionals.len() == 2 => Command::Debug(_file.to_string()),
+        (Some("debug"), None) => return Err("a path to a SAL executable must be provided".to_string()),
+        (Some("mood"), None) => Command::Mood,
+        (Some("mood"), Some("forecast")) if positionals.len() <= 4 => {
+            Command::Forecast(time(2)?.ok_or("mood forecast needs a time to start from")?, time(3)?)
+        }
+        (Some("mood"), Some("next")) if positionals.len() <= 4 => {
+            Command::Next(positional(2).ok_or("mood next needs a mood")?.parse()?, time(3)?)
+        }
+        (None, _) => return Err("a command must be provided".to_string()),
+        (Some(_command), _) if !["run", "debug", "mood"].contains(&_command) => {
+            return Err(format!("unknown command {}", _command))
         }
+        _ => return Err(format!("unexpected argument {}", positionals.last().unwrap())),
+    };
+    Ok(options)
+}
+
+// An RFC 3339 time, or a date meaning its midnight in UTC.
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(_time.with_timezone(&Utc));
     }
-}
\ No newline at end of file
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(_date) => Ok(Utc.from_utc_datetime(&_date.and_time(NaiveTime::MIN))),
+        Err(_) => Err(format!("expected a time like 2021-12-24T18:00:00Z or a date like 2021-12-24, not `{}`", value)),
+    }
+}
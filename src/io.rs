@@ -0,0 +1,211 @@
+/***
+ * Emulator for The Socially Acceptable Language
+ * - where ACCESS reads from and writes to
+ */
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Stdin, Stdout, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Input and output of `ACCESS`.
+///
+/// Reads give `None` at the end of input, see `EofPolicy` for what the machine does then,
+/// and an error of kind `InvalidData` for input that isn't what was asked for.
+pub trait Io: Debug {
+    fn read_integer(&mut self) -> io::Result<Option<i32>>;
+    fn read_character(&mut self) -> io::Result<Option<char>>;
+    fn write_integer(&mut self, value: i32) -> io::Result<()>;
+    fn write_character(&mut self, value: char) -> io::Result<()>;
+}
+
+/// What reading past the end of input does.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EofPolicy {
+    /// The registry is set to this value.
+    Sentinel(i32),
+    /// The machine refuses the instruction, ending the run.
+    Refuse,
+    /// The run ends with an input error.
+    #[default]
+    Error,
+}
+
+/// What writing a value that isn't a character does. Every Unicode scalar value is
+/// a character, so that is any value below zero, above `0x10FFFF`, or a surrogate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CharPolicy {
+    /// Nothing is written.
+    Skip,
+    /// `U+FFFD REPLACEMENT CHARACTER` is written instead.
+    #[default]
+    Replace,
+    /// The run ends with an output error.
+    Error,
+}
+
+impl std::str::FromStr for CharPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<CharPolicy, String> {
+        match policy {
+            "skip" => Ok(CharPolicy::Skip),
+            "replace" => Ok(CharPolicy::Replace),
+            "error" => Ok(CharPolicy::Error),
+            _ => Err(format!("expected skip, replace or error, not `{}`", policy)),
+        }
+    }
+}
+
+impl std::str::FromStr for EofPolicy {
+    type Err = String;
+
+    /// `error`, `refuse`, or a sentinel value.
+    fn from_str(policy: &str) -> Result<EofPolicy, String> {
+        match policy {
+            "error" => Ok(EofPolicy::Error),
+            "refuse" => Ok(EofPolicy::Refuse),
+            _ => policy
+                .parse::<i32>()
+                .map(EofPolicy::Sentinel)
+                .map_err(|_| format!("expected error, refuse or a number, not `{}`", policy)),
+        }
+    }
+}
+
+/// Reads from one stream and writes to another.
+///
+/// Integers are written one per line, characters are written as they are, in UTF-8.
+///
+/// Input is read a line at a time, and whatever part of it isn't used by one
+/// read is kept for the next:
+/// * Integers are separated by whitespace, including line breaks, and may be signed.
+///   An integer takes the one whitespace character ending it along with it.
+/// * Characters are read one at a time, line breaks included.
+pub struct Streams<R, W> {
+    pub input: R,
+    pub output: W,
+    unread: VecDeque<char>,
+}
+
+/// Standard input and output.
+pub type StdIo = Streams<BufReader<Stdin>, Stdout>;
+/// Input from memory, output to a `SharedBuffer`.
+pub type BufferIo = Streams<Cursor<Vec<u8>>, SharedBuffer>;
+/// Input from one file, output to another.
+pub type FileIo = Streams<BufReader<File>, File>;
+/// Any input and output, picked at run time.
+pub type BoxedIo = Streams<Box<dyn BufRead>, Box<dyn Write>>;
+
+pub fn stdio() -> StdIo {
+    Streams::new(BufReader::new(io::stdin()), io::stdout())
+}
+
+/// Io reading `input`, along with the buffer it writes to.
+pub fn buffer(input: &str) -> (BufferIo, SharedBuffer) {
+    let output = SharedBuffer::default();
+    (Streams::new(Cursor::new(input.as_bytes().to_vec()), output.clone()), output)
+}
+
+/// Io reading the file `input`, and writing over the file `output`.
+pub fn files(input: &Path, output: &Path) -> io::Result<FileIo> {
+    Ok(Streams::new(BufReader::new(File::open(input)?), File::create(output)?))
+}
+
+impl<R, W> Streams<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    pub fn new(input: R, output: W) -> Streams<R, W> {
+        Streams { input, output, unread: VecDeque::new() }
+    }
+
+    // The next character, reading another line if everything read so far is used. `None` at the end of input.
+    fn next_character(&mut self) -> io::Result<Option<char>> {
+        if self.unread.is_empty() {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.unread.extend(line.chars());
+        }
+        Ok(self.unread.pop_front())
+    }
+
+    fn write_line(&mut self, line: std::fmt::Arguments) -> io::Result<()> {
+        writeln!(self.output, "{}", line)?;
+        self.output.flush()
+    }
+}
+
+impl<R, W> Io for Streams<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    fn read_integer(&mut self) -> io::Result<Option<i32>> {
+        let mut token = String::new();
+        while let Some(_character) = self.next_character()? {
+            match _character {
+                _whitespace if _whitespace.is_whitespace() && token.is_empty() => continue,
+                _whitespace if _whitespace.is_whitespace() => break,
+                _ => token.push(_character),
+            }
+        }
+        if token.is_empty() {
+            return Ok(None);
+        }
+        match token.parse::<i32>() {
+            Ok(_value) => Ok(Some(_value)),
+            Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("expected an integer, not `{}`", token))),
+        }
+    }
+
+    fn read_character(&mut self) -> io::Result<Option<char>> {
+        self.next_character()
+    }
+
+    fn write_integer(&mut self, value: i32) -> io::Result<()> {
+        self.write_line(format_args!("{}", value))
+    }
+
+    fn write_character(&mut self, value: char) -> io::Result<()> {
+        write!(self.output, "{}", value)?;
+        self.output.flush()
+    }
+}
+
+impl<R, W> Debug for Streams<R, W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Streams").finish_non_exhaustive()
+    }
+}
+
+/// Output kept in memory, that can still be read while the machine writes to it.
+#[derive(Clone, Debug, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().map(|_bytes| _bytes.clone()).unwrap_or_default()
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.contents()).to_string()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        match self.0.lock() {
+            Ok(mut _buffer) => _buffer.write(bytes),
+            Err(_) => Err(io::Error::other("the buffer was poisoned")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
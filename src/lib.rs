@@ -0,0 +1,15 @@
+/***
+ * Emulator for The Socially Acceptable Language
+ */
+pub mod clock;
+pub mod debugger;
+pub mod emulator;
+pub mod io;
+pub mod machine;
+pub mod mood;
+pub mod timeline;
+pub mod trace;
+#[cfg(test)]
+mod tests;
+
+pub use machine::{Config, Machine, Mood, Outcome, RefusalPolicy, Tolerance};
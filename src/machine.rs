@@ -0,0 +1,950 @@
+/***
+ * Emulator for The Socially Acceptable Language
+ * - the machine running a program, one instruction at a time
+ */
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use sal_isa::{Instruction, Operation, Prefix, Registry, StackAction};
+
+use crate::clock::{Clock, RealClock, VirtualClock};
+use crate::io::{self, CharPolicy, EofPolicy, Io};
+use crate::mood;
+use crate::timeline::{Point, Timeline};
+use crate::trace::{Before, Trace};
+
+#[derive(Clone, PartialEq, Eq, Debug, Copy, Hash, Default)]
+pub enum Mood {
+    #[default]
+    Bored,
+    Happy,
+    Sick,
+    Maniacal,
+    Angry,
+    Annoyed,
+    Lovestruck,
+    Confused,
+}
+
+impl Mood {
+    /// Every mood, in the order the emulator picks them by number.
+    pub const ALL: [Mood; 8] = [
+        Mood::Bored,
+        Mood::Happy,
+        Mood::Sick,
+        Mood::Maniacal,
+        Mood::Angry,
+        Mood::Annoyed,
+        Mood::Lovestruck,
+        Mood::Confused,
+    ];
+
+    /// The mood picked by a random number, `number % 8`.
+    pub fn pick(number: u64) -> Mood {
+        Mood::ALL[(number % 8) as usize]
+    }
+}
+
+impl std::str::FromStr for Mood {
+    type Err = String;
+
+    /// A mood by its name, in any case.
+    fn from_str(name: &str) -> Result<Mood, String> {
+        Mood::ALL
+            .iter()
+            .copied()
+            .find(|_mood| format!("{:?}", _mood).eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("there is no mood called `{}`", name))
+    }
+}
+
+/// How a mood makes the machine react to the way it's asked to do things.
+#[derive(Clone, PartialEq, Eq, Debug, Copy)]
+pub struct Temperament {
+    /// The social credit a run starts with.
+    pub social_credit: i32,
+    pub small_tolerance: i32,
+    pub small_tolerance_close: i32,
+    pub medium_tolerance: i32,
+    pub medium_tolerance_close: i32,
+    pub large_tolerance: i32,
+    pub large_tolerance_close: i32,
+
+    // The speed at which different prefixes change your social credit and irritation.
+    pub polite_social_change: i32,
+    pub polite_strong_social_change: i32,
+    pub demanding_social_change: i32,
+    pub demanding_strong_social_change: i32,
+    pub irritation_change: i32,
+    pub irritation_decay: i32,
+}
+
+impl Temperament {
+    pub fn of(mood: Mood) -> Temperament {
+        let base = Temperament {
+            social_credit: 0,
+            small_tolerance: 50,
+            small_tolerance_close: 25,
+            medium_tolerance: 75,
+            medium_tolerance_close: 50,
+            large_tolerance: 100,
+            large_tolerance_close: 75,
+            polite_social_change: 2,
+            polite_strong_social_change: -5,
+            demanding_social_change: -2,
+            demanding_strong_social_change: -5,
+            irritation_change: 4,
+            irritation_decay: -1,
+        };
+        match mood {
+            Mood::Bored => base,
+            Mood::Happy => Temperament {
+                social_credit: 25,
+                small_tolerance: 75,
+                medium_tolerance: 100,
+                large_tolerance: 125,
+                small_tolerance_close: 50,
+                medium_tolerance_close: 75,
+                large_tolerance_close: 100,
+                ..base
+            },
+            Mood::Sick => Temperament {
+                social_credit: -25,
+                polite_strong_social_change: -7,
+                demanding_social_change: -4,
+                irritation_decay: 0,
+                ..base
+            },
+            Mood::Maniacal => Temperament {
+                polite_social_change: 0,
+                medium_tolerance_close: 0,
+                ..base
+            },
+            Mood::Angry => Temperament {
+                demanding_social_change: -4,
+                demanding_strong_social_change: -8,
+                polite_strong_social_change: -4,
+                large_tolerance: 75,
+                large_tolerance_close: 50,
+                ..base
+            },
+            Mood::Annoyed => Temperament {
+                irritation_change: 8,
+                ..base
+            },
+            Mood::Lovestruck => Temperament {
+                polite_social_change: 5,
+                polite_strong_social_change: -2,
+                social_credit: 25,
+                small_tolerance: 100,
+                medium_tolerance: 125,
+                large_tolerance: 150,
+                small_tolerance_close: 50,
+                medium_tolerance_close: 75,
+                large_tolerance_close: 100,
+                irritation_change: 1,
+                ..base
+            },
+            Mood::Confused => Temperament {
+                polite_social_change: -2,
+                polite_strong_social_change: -4,
+                demanding_social_change: 2,
+                demanding_strong_social_change: 5,
+                ..base
+            },
+        }
+    }
+}
+
+/// One of the tolerances of a `Temperament`. Social credit is past a tolerance
+/// when it is that far from zero, either way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub enum Tolerance {
+    SmallClose,
+    Small,
+    MediumClose,
+    Medium,
+    LargeClose,
+    Large,
+}
+
+impl Tolerance {
+    pub const ALL: [Tolerance; 6] =
+        [Tolerance::SmallClose, Tolerance::Small, Tolerance::MediumClose, Tolerance::Medium, Tolerance::LargeClose, Tolerance::Large];
+
+    pub fn of(&self, temperament: &Temperament) -> i32 {
+        match self {
+            Tolerance::SmallClose => temperament.small_tolerance_close,
+            Tolerance::Small => temperament.small_tolerance,
+            Tolerance::MediumClose => temperament.medium_tolerance_close,
+            Tolerance::Medium => temperament.medium_tolerance,
+            Tolerance::LargeClose => temperament.large_tolerance_close,
+            Tolerance::Large => temperament.large_tolerance,
+        }
+    }
+
+    /// Whether `credit` is past the tolerance.
+    pub fn is_past(&self, temperament: &Temperament, credit: i32) -> bool {
+        let tolerance = self.of(temperament);
+        credit >= tolerance || credit <= -tolerance
+    }
+}
+
+impl std::str::FromStr for Tolerance {
+    type Err = String;
+
+    /// `small`, `small-close`, `medium`, `medium-close`, `large` or `large-close`.
+    fn from_str(name: &str) -> Result<Tolerance, String> {
+        Tolerance::ALL
+            .iter()
+            .copied()
+            .find(|_tolerance| _tolerance.to_string() == name)
+            .ok_or_else(|| format!("expected small, medium or large, optionally followed by -close, not `{}`", name))
+    }
+}
+
+impl std::fmt::Display for Tolerance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Tolerance::SmallClose => write!(f, "small-close"),
+            Tolerance::Small => write!(f, "small"),
+            Tolerance::MediumClose => write!(f, "medium-close"),
+            Tolerance::Medium => write!(f, "medium"),
+            Tolerance::LargeClose => write!(f, "large-close"),
+            Tolerance::Large => write!(f, "large"),
+        }
+    }
+}
+
+/// Everything a run needs to know besides the program.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Config {
+    pub mood: Mood,
+    /// When set, the mood is the one picked with this seed instead, see `mood::mood_of`.
+    pub seed: Option<u64>,
+    /// Sulk on a `VirtualClock` instead of really blocking.
+    pub virtual_time: bool,
+    /// What reading past the end of input does.
+    pub eof: EofPolicy,
+    /// What writing a value that isn't a character does.
+    pub invalid_char: CharPolicy,
+    /// What refusing a polite instruction does.
+    pub refusal: RefusalPolicy,
+    /// Instructions to run at most, see `Outcome::OutOfSteps`.
+    pub max_steps: Option<u64>,
+    /// How long `Machine::run` may take, sulking included, see `Outcome::TimedOut`.
+    pub time_limit: Option<Duration>,
+    /// Look out for the machine going back to a state it was in before, see `Outcome::Looping`.
+    pub detect_loops: bool,
+}
+
+/// What the machine does when it refuses a polite instruction, past the small tolerance.
+///
+/// Social credit doesn't change while the machine refuses, so retrying only helps
+/// if whoever is running it changes something in between.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RefusalPolicy {
+    /// End the run with `Outcome::Refused`.
+    #[default]
+    Abort,
+    /// Move on to the next instruction without running it.
+    Skip,
+    /// Sulk for `backoff`, twice as long every time after that, and try again.
+    /// After `attempts` tries, end the run with `Outcome::Refused`.
+    Retry { attempts: u32, backoff: Duration },
+}
+
+impl std::str::FromStr for RefusalPolicy {
+    type Err = String;
+
+    /// `abort`, `skip`, or `retry`, optionally followed by `:<attempts>` and `:<backoff in milliseconds>`.
+    fn from_str(policy: &str) -> Result<RefusalPolicy, String> {
+        let mut parts = policy.split(':');
+        let error = || format!("expected abort, skip or retry[:<attempts>[:<milliseconds>]], not `{}`", policy);
+        let policy = match parts.next() {
+            Some("abort") => RefusalPolicy::Abort,
+            Some("skip") => RefusalPolicy::Skip,
+            Some("retry") => {
+                let attempts = parts.next().map(str::parse).transpose().map_err(|_| error())?;
+                let backoff = parts.next().map(str::parse).transpose().map_err(|_| error())?;
+                RefusalPolicy::Retry {
+                    attempts: attempts.unwrap_or(RETRY_ATTEMPTS),
+                    backoff: Duration::from_millis(backoff.unwrap_or(RETRY_BACKOFF_MS)),
+                }
+            }
+            _ => return Err(error()),
+        };
+        match parts.next() {
+            Some(_) => Err(error()),
+            None => Ok(policy),
+        }
+    }
+}
+
+/// Tries `retry` makes when not told how many.
+pub const RETRY_ATTEMPTS: u32 = 3;
+/// First back-off of `retry` when not told how long.
+pub const RETRY_BACKOFF_MS: u64 = 500;
+
+/// How a run ended. Every way has its own exit status, see `exit_code`.
+#[derive(Clone, PartialEq, Eq, Debug, Copy)]
+pub enum Outcome {
+    /// The program ran past its last instruction.
+    Halted,
+    /// A polite instruction was asked for when social credit was past the small tolerance,
+    /// and `RefusalPolicy` said to give up, or input ran out with `EofPolicy::Refuse`.
+    /// The machine is still at that instruction.
+    Refused,
+    /// Irritation reached `IRRITATION_LIMIT`.
+    FedUp,
+    /// Begging past the large tolerance.
+    WalkedOut,
+    /// An order was given past the medium tolerance.
+    RageQuit,
+    /// `ACCESS` could not read or write, see `Machine::io_error`.
+    /// The machine is still at that instruction.
+    IoError,
+    /// A jump would have landed outside the program. Landing right after its last
+    /// instruction is fine, that halts it. The machine is still at the jump.
+    JumpOutOfBounds {
+        /// Address of the jump.
+        address: usize,
+        /// What the jump moved by, doubled already if it was.
+        offset: usize,
+        prefix: Prefix,
+        /// Whether the order was carried out twice over, near the medium tolerance.
+        doubled: bool,
+    },
+    /// `Config::max_steps` instructions were run and the program wanted to go on.
+    OutOfSteps,
+    /// `Machine::run` took longer than `Config::time_limit`.
+    TimedOut,
+    /// The machine was back in a state it had been in before, without reading any input
+    /// in between, so it would have gone round the same way forever.
+    /// Only noticed with `Config::detect_loops`.
+    Looping,
+}
+
+impl Outcome {
+    /// The exit status of the emulator when a run ends this way:
+    ///
+    /// | **Outcome** | **Exit status** |
+    /// |:------------|:----------------|
+    /// | Halted | 0 |
+    /// | Refused | 10 |
+    /// | FedUp | 11 |
+    /// | WalkedOut | 12 |
+    /// | RageQuit | 13 |
+    /// | IoError | 14 |
+    /// | JumpOutOfBounds | 15 |
+    /// | OutOfSteps | 16 |
+    /// | TimedOut | 17 |
+    /// | Looping | 18 |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Halted => 0,
+            Outcome::Refused => 10,
+            Outcome::FedUp => 11,
+            Outcome::WalkedOut => 12,
+            Outcome::RageQuit => 13,
+            Outcome::IoError => 14,
+            Outcome::JumpOutOfBounds { .. } => 15,
+            Outcome::OutOfSteps => 16,
+            Outcome::TimedOut => 17,
+            Outcome::Looping => 18,
+        }
+    }
+
+    /// What happened, from the machine's point of view.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Outcome::Halted => "the program ran to its end",
+            Outcome::Refused => "the program asked politely once too often",
+            Outcome::FedUp => "the machine is done with your wishy-washy attitude",
+            Outcome::WalkedOut => "the machine got tired of your snivelling",
+            Outcome::RageQuit => "the machine would not take one more order",
+            Outcome::IoError => "the program's input or output failed",
+            Outcome::JumpOutOfBounds { .. } => "the program jumped out of itself",
+            Outcome::OutOfSteps => "the program ran as many instructions as it was allowed",
+            Outcome::TimedOut => "the program ran for as long as it was allowed",
+            Outcome::Looping => "the program would have gone round in circles forever",
+        }
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Outcome::Halted => write!(f, "Halted"),
+            Outcome::Refused => write!(f, "Refused"),
+            Outcome::FedUp => write!(f, "Fed up"),
+            Outcome::WalkedOut => write!(f, "Walked out"),
+            Outcome::RageQuit => write!(f, "Rage quit"),
+            Outcome::IoError => write!(f, "Input or output error"),
+            Outcome::JumpOutOfBounds { .. } => write!(f, "Jump out of bounds"),
+            Outcome::OutOfSteps => write!(f, "Out of steps"),
+            Outcome::TimedOut => write!(f, "Timed out"),
+            Outcome::Looping => write!(f, "Looping"),
+        }
+    }
+}
+
+/// Irritation at which the machine is done with you.
+pub const IRRITATION_LIMIT: i32 = 1000;
+
+/// ## Machine
+/// The state of a SAL program being run. Meant to emulate bespoke Assembly code for a custom-made chipset.
+///
+/// Polite and demanding prefixes each have their own pair of registries, while
+/// the two stacks and the two loops are shared.
+#[derive(Debug)]
+pub struct Machine {
+    program: Vec<Instruction>,
+    /// Address of the next instruction.
+    pub pc: usize,
+    /// Instructions run so far, refused ones not counted.
+    pub steps: u64,
+    /// Instructions refused so far, every retry counted.
+    pub refusals: u64,
+    // Times the instruction at `pc` has been retried.
+    retries: u32,
+    pub polite_registries: (i32, i32),
+    pub demanding_registries: (i32, i32),
+    pub stacks: (Vec<i32>, Vec<i32>),
+    /// Address of the `STARTING HERE` of each loop.
+    pub loops: (usize, usize),
+    pub loop_counters: (i32, i32),
+    pub loop_registries: (i32, i32),
+    pub social_credit: i32,
+    pub irritation: i32,
+    pub last_was_positive: bool,
+    pub mood: Mood,
+    pub temperament: Temperament,
+    /// Total time spent sulking so far.
+    pub sulked: Duration,
+    /// What went wrong, if the run ended with `Outcome::IoError`.
+    pub io_error: Option<String>,
+    /// What the machine had to say about the last instruction, if anything.
+    pub remark: Option<&'static str>,
+    // Everything it said since `take_remarks`.
+    remarks: Vec<&'static str>,
+    eof: EofPolicy,
+    invalid_char: CharPolicy,
+    refusal: RefusalPolicy,
+    max_steps: Option<u64>,
+    time_limit: Option<Duration>,
+    // Only kept while `run` has a time limit.
+    deadline: Option<Deadline>,
+    // Only kept with `Config::detect_loops`.
+    loop_detector: Option<LoopDetector>,
+    clock: Box<dyn Clock>,
+    io: Box<dyn Io>,
+    trace: Option<Trace>,
+    timeline: Option<Timeline>,
+}
+
+impl Machine {
+    /// A machine about to run an executable.
+    pub fn new(executable: &[u8], config: Config) -> Machine {
+        let mood = config.seed.map(mood::mood_of).unwrap_or(config.mood);
+        let temperament = Temperament::of(mood);
+        Machine {
+            program: executable.iter().map(|_byte| sal_isa::decode(*_byte)).collect(),
+            pc: 0,
+            steps: 0,
+            refusals: 0,
+            retries: 0,
+            polite_registries: (0, 0),
+            demanding_registries: (0, 0),
+            stacks: (Vec::new(), Vec::new()),
+            loops: (0, 0),
+            loop_counters: (0, 0),
+            loop_registries: (0, 0),
+            social_credit: temperament.social_credit,
+            irritation: 0,
+            last_was_positive: true,
+            mood,
+            temperament,
+            sulked: Duration::ZERO,
+            io_error: None,
+            remark: None,
+            remarks: Vec::new(),
+            eof: config.eof,
+            invalid_char: config.invalid_char,
+            refusal: config.refusal,
+            max_steps: config.max_steps,
+            time_limit: config.time_limit,
+            deadline: None,
+            loop_detector: if config.detect_loops { Some(LoopDetector::new()) } else { None },
+            clock: if config.virtual_time { Box::new(VirtualClock::default()) } else { Box::new(RealClock) },
+            io: Box::new(io::stdio()),
+            trace: None,
+            timeline: None,
+        }
+    }
+
+    /// Read and write somewhere other than standard input and output.
+    pub fn with_io(mut self, io: Box<dyn Io>) -> Machine {
+        self.io = io;
+        self
+    }
+
+    /// Write every instruction run to a trace.
+    pub fn with_trace(mut self, trace: Trace) -> Machine {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Keep the social state after every instruction run.
+    pub fn with_timeline(mut self) -> Machine {
+        self.timeline = Some(Timeline::new(&self));
+        self
+    }
+
+    /// The timeline kept since `with_timeline`.
+    pub fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_ref()
+    }
+
+    /// Sulk on another clock.
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Machine {
+        self.clock = clock;
+        self
+    }
+
+    pub fn program(&self) -> &[Instruction] {
+        &self.program
+    }
+
+    /// Run until the program ends one way or another.
+    pub fn run(&mut self) -> Outcome {
+        self.run_with(|_| ())
+    }
+
+    /// `run`, passing on everything the machine has to say as soon as it says it.
+    pub fn run_with_remarks(&mut self, mut remark: impl FnMut(&'static str)) -> Outcome {
+        self.run_with(|_machine| _machine.take_remarks().into_iter().for_each(&mut remark))
+    }
+
+    /// What the machine said since this was last called, oldest first.
+    pub fn take_remarks(&mut self) -> Vec<&'static str> {
+        std::mem::take(&mut self.remarks)
+    }
+
+    // `run`, calling `after_step` after every instruction.
+    fn run_with(&mut self, mut after_step: impl FnMut(&mut Machine)) -> Outcome {
+        self.deadline = self.time_limit.map(|_limit| Deadline { limit: _limit, started: Instant::now(), sulked: self.sulked, slept: Duration::ZERO });
+        let outcome = loop {
+            let stepped = self.step();
+            after_step(self);
+            if let Some(_outcome) = stepped {
+                break _outcome;
+            }
+            if self.deadline.as_ref().is_some_and(|_deadline| _deadline.left(self.sulked).is_zero()) {
+                break Outcome::TimedOut;
+            }
+        };
+        self.deadline = None;
+        outcome
+    }
+
+    /// Run the instruction at `pc`. `None` if the program can go on.
+    pub fn step(&mut self) -> Option<Outcome> {
+        if self.trace.is_none() && self.timeline.is_none() {
+            return self.advance().0;
+        }
+        let before = Before::of(self);
+        let (mut outcome, doubled) = self.advance();
+        let instruction = match self.program.get(before.pc) {
+            Some(_instruction) if outcome != Some(Outcome::OutOfSteps) => *_instruction,
+            _ => return outcome,
+        };
+        if let Some(mut _trace) = self.trace.take() {
+            let mut written = _trace.record(&before, self, instruction, doubled, outcome);
+            if outcome.is_some() {
+                written = written.and_then(|_| _trace.flush());
+            }
+            if let Err(_error) = written {
+                self.io_error = Some(format!("failed to write the trace: {}", _error));
+                outcome = Some(Outcome::IoError);
+            }
+            self.trace = Some(_trace);
+        }
+        let point = Point::of(self);
+        if let Some(_timeline) = &mut self.timeline {
+            _timeline.points.push(point);
+        }
+        outcome
+    }
+
+    // `step`, without tracing, along with whether the instruction was carried out twice over.
+    fn advance(&mut self) -> (Option<Outcome>, bool) {
+        let Instruction { prefix, operation } = match self.program.get(self.pc) {
+            Some(_instruction) => *_instruction,
+            None => return (Some(Outcome::Halted), false),
+        };
+        if self.max_steps.is_some_and(|_max_steps| self.steps >= _max_steps) {
+            return (Some(Outcome::OutOfSteps), false);
+        }
+        let temperament = self.temperament;
+        let credit = self.social_credit;
+        let past = |tolerance: i32| credit >= tolerance || credit <= -tolerance;
+        self.remark = None;
+
+        // Here we first check various social values, to determine if the operation is changed.
+        if self.irritation >= IRRITATION_LIMIT {
+            self.say("This program is DONE with your wishy-washy attitude.");
+            self.sulk(500);
+            return (Some(Outcome::FedUp), false);
+        }
+
+        let social_change = match prefix {
+            Prefix::Please => {
+                if past(temperament.small_tolerance) {
+                    return (self.refuse(), false);
+                }
+                if past(temperament.small_tolerance_close) {
+                    self.say("\"I guess...\"");
+                }
+                temperament.polite_social_change
+            }
+            Prefix::Begging => {
+                if past(temperament.large_tolerance) {
+                    self.say("The program got tired of your snivelling attitude and left...");
+                    self.sulk(500);
+                    return (Some(Outcome::WalkedOut), false);
+                }
+                if past(temperament.large_tolerance_close) {
+                    self.sulk(1500);
+                }
+                temperament.polite_strong_social_change
+            }
+            Prefix::Now => {
+                if past(temperament.medium_tolerance) {
+                    self.say("\"Oh, now? Really, now?\" The program is defiantly doing nothing. \"Fine.\"");
+                    self.sulk(15000);
+                }
+                if past(temperament.medium_tolerance_close) {
+                    self.sulk(500);
+                }
+                temperament.demanding_social_change
+            }
+            Prefix::Ordering => {
+                if past(temperament.medium_tolerance) {
+                    self.say("\"No, you know what, f*** you!\"");
+                    self.sulk(1000);
+                    return (Some(Outcome::RageQuit), false);
+                }
+                temperament.demanding_strong_social_change
+            }
+        };
+
+        // Orders given close to the medium tolerance are carried out twice over.
+        let doubling = prefix == Prefix::Ordering && past(temperament.medium_tolerance_close);
+        let doubled = match self.execute(prefix, operation, doubling) {
+            Ok(_doubled) => _doubled,
+            Err(_outcome @ Outcome::JumpOutOfBounds { doubled, .. }) => return (Some(_outcome), doubled),
+            Err(_outcome) => return (Some(_outcome), false),
+        };
+        // An instruction that failed is still the one at `pc`, and is only charged for once it is carried out.
+        self.irritate(prefix == Prefix::Please);
+        self.social_credit += social_change;
+        self.pc = self.pc.wrapping_add(1);
+        self.steps += 1;
+        self.retries = 0;
+        if self.is_looping() {
+            return (Some(Outcome::Looping), doubled);
+        }
+        (None, doubled)
+    }
+
+    // Whether the machine is back in a state `loop_detector` saw before.
+    fn is_looping(&mut self) -> bool {
+        let seen = match &self.loop_detector {
+            Some(_detector) => _detector.saved.as_ref().is_some_and(|_state| _state.is_of(self)),
+            None => return false,
+        };
+        if !seen {
+            let state = State::of(self);
+            if let Some(_detector) = &mut self.loop_detector {
+                _detector.pass(state);
+            }
+        }
+        seen
+    }
+
+    // Refuse the instruction at `pc`, and do what `RefusalPolicy` says.
+    fn refuse(&mut self) -> Option<Outcome> {
+        self.refusals += 1;
+        match self.refusal {
+            RefusalPolicy::Abort => Some(Outcome::Refused),
+            RefusalPolicy::Skip => {
+                self.pc = self.pc.wrapping_add(1);
+                None
+            }
+            RefusalPolicy::Retry { attempts, backoff } if self.retries < attempts => {
+                let wait = backoff.as_millis() as u64 * 2u64.saturating_pow(self.retries);
+                self.retries += 1;
+                self.sulk(wait);
+                None
+            }
+            RefusalPolicy::Retry { .. } => Some(Outcome::Refused),
+        }
+    }
+
+    // Remark on the instruction being run.
+    fn say(&mut self, remark: &'static str) {
+        self.remark = Some(remark);
+        self.remarks.push(remark);
+    }
+
+    // Sulking is cut short at the time limit of the run, if there is one.
+    fn sulk(&mut self, milliseconds: u64) {
+        let mut duration = Duration::from_millis(milliseconds);
+        if let Some(_deadline) = &self.deadline {
+            duration = duration.min(_deadline.left(self.sulked));
+        }
+        self.sulked += duration;
+        let sleeping = Instant::now();
+        self.clock.sleep(duration);
+        if let Some(_deadline) = &mut self.deadline {
+            _deadline.slept += sleeping.elapsed();
+        }
+    }
+
+    // Switching between asking nicely and not is irritating, keeping at it slowly calms the machine down.
+    fn irritate(&mut self, positive: bool) {
+        if self.last_was_positive != positive {
+            self.irritation += self.temperament.irritation_change;
+            self.last_was_positive = positive;
+        } else if self.irritation > 0 {
+            self.irritation += self.temperament.irritation_decay;
+        }
+    }
+
+    // Whether the instruction was carried out twice over, which `doubling` asks for but only
+    // increments and jumps taken do. `Err` if the instruction ends the run instead.
+    fn execute(&mut self, prefix: Prefix, operation: Operation, doubling: bool) -> Result<bool, Outcome> {
+        let mut doubled = false;
+        let registries = if prefix.is_polite() { &mut self.polite_registries } else { &mut self.demanding_registries };
+
+        match operation {
+            Operation::Increment { registry, negatively, using_other_registry } => {
+                let (selected_registry, other_registry) = select(registries, registry);
+                let mut value = if using_other_registry { other_registry } else { 1 };
+                // Registries wrap around, like the 32-bit registers they stand for.
+                if doubling {
+                    value = value.wrapping_mul(2);
+                    doubled = true;
+                }
+                if negatively {
+                    *selected_registry = selected_registry.wrapping_sub(value);
+                } else {
+                    *selected_registry = selected_registry.wrapping_add(value);
+                }
+            }
+            Operation::To { registry, action } => {
+                let (selected_registry, _) = select(registries, registry);
+                let selected_stack = match registry {
+                    Registry::First => &mut self.stacks.0,
+                    Registry::Second => &mut self.stacks.1,
+                };
+                match action {
+                    StackAction::Double => *selected_registry = selected_registry.wrapping_mul(2),
+                    StackAction::Halve => *selected_registry /= 2,
+                    StackAction::Push => selected_stack.push(*selected_registry),
+                    StackAction::Pop => *selected_registry = selected_stack.pop().unwrap_or(0),
+                }
+            }
+            Operation::Access { registry, outputting, as_character } => {
+                let (selected_registry, _) = select(registries, registry);
+                let result = if !outputting {
+                    // Whatever is read next may be different, so states from before it don't count.
+                    if self.loop_detector.is_some() {
+                        self.loop_detector = Some(LoopDetector::new());
+                    }
+                    let read = if as_character {
+                        self.io.read_character().map(|_character| _character.map(|_character| _character as i32))
+                    } else {
+                        self.io.read_integer()
+                    };
+                    match (read, self.eof) {
+                        (Ok(Some(_value)), _) | (Ok(None), EofPolicy::Sentinel(_value)) => {
+                            *selected_registry = _value;
+                            Ok(())
+                        }
+                        (Ok(None), EofPolicy::Refuse) => {
+                            self.refusals += 1;
+                            return Err(Outcome::Refused);
+                        }
+                        (Ok(None), EofPolicy::Error) => Err("there is no more input".to_string()),
+                        (Err(_error), _) => Err(format!("failed to read input: {}", _error)),
+                    }
+                } else if as_character {
+                    let character = u32::try_from(*selected_registry).ok().and_then(char::from_u32);
+                    let character = match (character, self.invalid_char) {
+                        (Some(_character), _) => Ok(Some(_character)),
+                        (None, CharPolicy::Replace) => Ok(Some(char::REPLACEMENT_CHARACTER)),
+                        (None, CharPolicy::Skip) => Ok(None),
+                        (None, CharPolicy::Error) => Err(format!("{} is not a character", selected_registry)),
+                    };
+                    match character {
+                        Ok(Some(_character)) => {
+                            self.io.write_character(_character).map_err(|_error| format!("failed to write output: {}", _error))
+                        }
+                        Ok(None) => Ok(()),
+                        Err(_message) => Err(_message),
+                    }
+                } else {
+                    self.io.write_integer(*selected_registry).map_err(|_error| format!("failed to write output: {}", _error))
+                };
+                if let Err(_message) = result {
+                    self.io_error = Some(_message);
+                    return Err(Outcome::IoError);
+                }
+            }
+            Operation::Repeat { registry, ending, second_loop } => {
+                let (selected_registry, _) = select(registries, registry);
+                let (start, counter, times) = if second_loop {
+                    (&mut self.loops.1, &mut self.loop_counters.1, &mut self.loop_registries.1)
+                } else {
+                    (&mut self.loops.0, &mut self.loop_counters.0, &mut self.loop_registries.0)
+                };
+                if ending {
+                    if *counter < *times {
+                        *counter += 1;
+                        self.pc = *start;
+                    }
+                } else {
+                    *counter = 0;
+                    *times = *selected_registry;
+                    *start = self.pc;
+                }
+            }
+            _ => {
+                let jumps = match operation {
+                    Operation::BranchIfGreater { registry, .. } => {
+                        let (selected_registry, other_registry) = select(registries, registry);
+                        *selected_registry > other_registry
+                    }
+                    Operation::BranchIfZero { registry, .. } => *select(registries, registry).0 == 0,
+                    Operation::JumpIfEqual { .. } => registries.0 == registries.1,
+                    _ => true,
+                };
+                if jumps {
+                    // Every jump moves by `sal_isa::offset`, and then on by one like any other instruction.
+                    let mut value = sal_isa::offset(operation.distance().unwrap_or(0));
+                    if doubling {
+                        value *= 2;
+                        doubled = true;
+                    }
+                    match sal_isa::target(prefix, self.pc, value) {
+                        Some(_target) if _target <= self.program.len() => self.pc = _target.wrapping_sub(1),
+                        _ => return Err(Outcome::JumpOutOfBounds { address: self.pc, offset: value, prefix, doubled }),
+                    }
+                }
+            }
+        }
+        Ok(doubled)
+    }
+}
+
+// Everything deciding what the machine does next, apart from its input.
+#[derive(Clone, PartialEq, Debug)]
+struct State {
+    pc: usize,
+    polite_registries: (i32, i32),
+    demanding_registries: (i32, i32),
+    stacks: (Vec<i32>, Vec<i32>),
+    loops: (usize, usize),
+    loop_counters: (i32, i32),
+    loop_registries: (i32, i32),
+    social_credit: i32,
+    irritation: i32,
+    last_was_positive: bool,
+}
+
+impl State {
+    fn of(machine: &Machine) -> State {
+        State {
+            pc: machine.pc,
+            polite_registries: machine.polite_registries,
+            demanding_registries: machine.demanding_registries,
+            stacks: machine.stacks.clone(),
+            loops: machine.loops,
+            loop_counters: machine.loop_counters,
+            loop_registries: machine.loop_registries,
+            social_credit: machine.social_credit,
+            irritation: machine.irritation,
+            last_was_positive: machine.last_was_positive,
+        }
+    }
+
+    // Compared field by field, so the stacks aren't copied on every instruction.
+    fn is_of(&self, machine: &Machine) -> bool {
+        self.pc == machine.pc
+            && self.polite_registries == machine.polite_registries
+            && self.demanding_registries == machine.demanding_registries
+            && self.stacks == machine.stacks
+            && self.loops == machine.loops
+            && self.loop_counters == machine.loop_counters
+            && self.loop_registries == machine.loop_registries
+            && self.social_credit == machine.social_credit
+            && self.irritation == machine.irritation
+            && self.last_was_positive == machine.last_was_positive
+    }
+}
+
+// Brent's cycle detection. One state is saved and every state after it is compared with it,
+// saving a new one after 1, 2, 4, 8... states, so a loop is noticed within twice its length
+// of going round it, whatever came before it, without keeping every state.
+#[derive(Debug)]
+struct LoopDetector {
+    saved: Option<State>,
+    passed: u64,
+    power: u64,
+}
+
+impl LoopDetector {
+    fn new() -> LoopDetector {
+        LoopDetector { saved: None, passed: 0, power: 1 }
+    }
+
+    // A state that isn't the saved one went by.
+    fn pass(&mut self, state: State) {
+        self.passed += 1;
+        if self.saved.is_none() || self.passed >= self.power {
+            self.saved = Some(state);
+            self.passed = 0;
+            self.power *= 2;
+        }
+    }
+}
+
+// The time limit of a run. The run takes the time spent running instructions, plus the time
+// spent sulking however the clock sulks, so a virtual clock's sulking counts as well.
+#[derive(Debug)]
+struct Deadline {
+    limit: Duration,
+    started: Instant,
+    // `Machine::sulked` when the run started.
+    sulked: Duration,
+    // Time actually spent in `Clock::sleep` since the run started.
+    slept: Duration,
+}
+
+impl Deadline {
+    // Time left before the limit, given `Machine::sulked`.
+    fn left(&self, sulked: Duration) -> Duration {
+        let taken = self.started.elapsed().saturating_sub(self.slept) + (sulked - self.sulked);
+        self.limit.saturating_sub(taken)
+    }
+}
+
+// The selected registry, and the other one.
+fn select(registries: &mut (i32, i32), registry: Registry) -> (&mut i32, i32) {
+    match registry {
+        Registry::First => (&mut registries.0, registries.1),
+        Registry::Second => (&mut registries.1, registries.0),
+    }
+}
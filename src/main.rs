@@ -1,5 +1,5 @@
-mod emulator;
+use murnion_task_9::emulator;
 
 fn main() {
-    emulator::emulate();
-}
\ No newline at end of file
+    std::process::exit(emulator::emulate(std::env::args().skip(1).collect()));
+}
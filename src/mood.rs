@@ -0,0 +1,49 @@
+/***
+ * Emulator for The Socially Acceptable Language
+ * - how the emulator picks its mood
+ */
+use chrono::prelude::*;
+use chrono::{Duration, DurationRound};
+use rand::rngs;
+use rand::{Rng, SeedableRng};
+
+use crate::machine::Mood;
+
+/// The seed of the mood at a time. It only changes every hour, `hour * day of the year`.
+pub fn seed_at(time: DateTime<Utc>) -> u64 {
+    let hours = time.time().hour();
+    let days = time.date_naive().ordinal();
+    (hours * days) as u64
+}
+
+/// The mood picked with a seed.
+pub fn mood_of(seed: u64) -> Mood {
+    let mut rng = rngs::StdRng::seed_from_u64(seed);
+    Mood::pick(rng.gen::<u64>())
+}
+
+/// The mood the emulator is in at a time.
+pub fn mood_at(time: DateTime<Utc>) -> Mood {
+    mood_of(seed_at(time))
+}
+
+/// Years the search for a mood looks ahead. Seeds repeat every year, apart from
+/// the 366th day of leap years, so if a mood doesn't come up within five it never will.
+const SEARCH_YEARS: i64 = 5;
+
+/// The mood of every hour from the one `from` is in, up to `to`.
+pub fn forecast(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<(DateTime<Utc>, Mood)> {
+    hours(from).take_while(|_hour| *_hour < to).map(|_hour| (_hour, mood_at(_hour))).collect()
+}
+
+/// The first hour, starting with the one `after` is in, that the emulator is in a mood.
+/// `None` if it never is.
+pub fn next(mood: Mood, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    hours(after).take((SEARCH_YEARS * 366 * 24) as usize).find(|_hour| mood_at(*_hour) == mood)
+}
+
+// Every hour from the start of the one `from` is in.
+fn hours(from: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> {
+    let start = from.duration_trunc(Duration::hours(1)).unwrap_or(from);
+    (0..).map(move |_hour| start + Duration::hours(_hour))
+}
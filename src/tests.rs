@@ -0,0 +1,673 @@
+use the_socially_acceptable_language_compiler::compile_str;
+use std::time::Duration;
+use chrono::prelude::*;
+use crate::clock::VirtualClock;
+use crate::debugger::{Debugger, Stop};
+use crate::emulator::{emulate, IO_ERROR, SUCCESS, USAGE_ERROR};
+use crate::io::{self, CharPolicy, EofPolicy};
+use crate::machine::{Config, Machine, Mood, Outcome, RefusalPolicy, Tolerance};
+use crate::mood;
+use crate::timeline::Point;
+use crate::trace::Trace;
+use sal_isa::{Instruction, Operation, Prefix, Registry};
+
+fn machine(source: &str, mood: Mood) -> Machine {
+    Machine::new(&compile_str(source).unwrap().bytes, Config { mood, virtual_time: true, ..Config::default() })
+}
+
+// An executable the compiler wouldn't write.
+fn encode(instructions: &[(Prefix, Operation)]) -> Vec<u8> {
+    instructions.iter().map(|&(prefix, operation)| sal_isa::encode(&Instruction { prefix, operation }).unwrap()).collect()
+}
+
+#[test]
+fn runs_to_the_end() {
+    let mut machine = machine(
+        "PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+         PLEASE, TO, THE FIRST REGISTRY, DOUBLE THE VALUE.\n\
+         NOW, INCREMENT, THE SECOND REGISTRY, NEGATIVELY, USING ONE.\n",
+        Mood::Bored,
+    );
+    assert_eq!(machine.run(), Outcome::Halted);
+    assert_eq!(machine.polite_registries, (2, 0));
+    assert_eq!(machine.demanding_registries, (0, -1));
+    assert_eq!(machine.pc, 3);
+    assert_eq!(machine.step(), Some(Outcome::Halted));
+}
+
+#[test]
+fn steps_one_instruction_at_a_time() {
+    let mut machine = machine(
+        "PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+         PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n",
+        Mood::Happy,
+    );
+    assert_eq!(machine.social_credit, 25);
+    assert_eq!(machine.step(), None);
+    assert_eq!((machine.pc, machine.polite_registries.0, machine.social_credit), (1, 1, 27));
+}
+
+#[test]
+fn branches_land_on_their_label() {
+    let mut machine = machine(
+        "PLEASE, IF THE SPECIFIED REGISTRY IS EQUAL TO ZERO JUMP TO THE SPECIFIED LABEL, COMPARING THE FIRST REGISTRY, AND JUMPING TO THE END.\n\
+         PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+         PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+         THE END:\n\
+         PLEASE, INCREMENT, THE SECOND REGISTRY, POSITIVELY, USING ONE.\n",
+        Mood::Bored,
+    );
+    assert_eq!(machine.run(), Outcome::Halted);
+    assert_eq!(machine.polite_registries, (0, 1));
+}
+
+#[test]
+fn loops_repeat() {
+    let mut machine = machine(
+        "PLEASE, INCREMENT, THE SECOND REGISTRY, POSITIVELY, USING ONE.\n\
+         PLEASE, TO, THE SECOND REGISTRY, DOUBLE THE VALUE.\n\
+         PLEASE, REPEAT THESE INSTRUCTIONS AN AMOUNT OF TIMES EQUAL TO, THE SECOND REGISTRY, STARTING HERE, AND THIS IS THE FIRST LOOP.\n\
+         PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+         PLEASE, REPEAT THESE INSTRUCTIONS AN AMOUNT OF TIMES EQUAL TO, THE SECOND REGISTRY, ENDING HERE, AND THIS IS THE FIRST LOOP.\n",
+        Mood::Lovestruck,
+    );
+    assert_eq!(machine.run(), Outcome::Halted);
+    assert_eq!(machine.polite_registries, (3, 2));
+}
+
+#[test]
+fn polite_requests_are_refused_past_the_small_tolerance() {
+    let mut machine = machine(&"PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(30), Mood::Bored);
+    assert_eq!(machine.run(), Outcome::Refused);
+    assert_eq!((machine.pc, machine.social_credit, machine.polite_registries.0), (25, 50, 25));
+    assert_eq!(machine.steps, 25);
+}
+
+#[test]
+fn remarks_are_passed_on_as_they_are_made() {
+    let source = "PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(30);
+    let mut remarks = Vec::new();
+    assert_eq!(machine(&source, Mood::Bored).run_with_remarks(|_remark| remarks.push(_remark)), Outcome::Refused);
+    assert_eq!(remarks, ["\"I guess...\""; 12]);
+
+    // Stepping, they wait to be taken, one for each instruction close to the small tolerance.
+    let mut stepped = machine(&source, Mood::Bored);
+    let mut remarked = Vec::new();
+    while stepped.step().is_none() {
+        if !stepped.take_remarks().is_empty() {
+            remarked.push(stepped.steps);
+        }
+    }
+    assert_eq!(remarked, (14..=25).collect::<Vec<u64>>());
+    assert_eq!(stepped.take_remarks(), Vec::<&str>::new());
+}
+
+#[test]
+fn relaxed_branches_behave_past_the_small_tolerance() {
+    // The branch can't reach the end, so it goes through a trampoline after the second jump.
+    let source = |registry: &str| {
+        format!(
+            "{}I'M BEGGING YOU, IF THE SPECIFIED REGISTRY IS EQUAL TO ZERO JUMP TO THE SPECIFIED LABEL, COMPARING THE {} REGISTRY, AND JUMPING TO THE END.\n\
+             I'M BEGGING YOU, JUMP TO, THE FIRST BLOCK.\n\
+             NOW, INCREMENT, THE SECOND REGISTRY, NEGATIVELY, USING ONE.\n\
+             THE FIRST BLOCK:\n\
+             NOW, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+             I'M BEGGING YOU, JUMP TO, THE SECOND BLOCK.\n\
+             NOW, INCREMENT, THE SECOND REGISTRY, NEGATIVELY, USING ONE.\n\
+             THE SECOND BLOCK:\n\
+             NOW, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+             THE END:\n\
+             NOW, INCREMENT, THE SECOND REGISTRY, POSITIVELY, USING ONE.\n",
+            "I'M BEGGING YOU, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(11),
+            registry
+        )
+    };
+    assert_eq!(compile_str(&source("FIRST")).unwrap().relaxed_bytes, 1);
+
+    // Well past the small tolerance, where anything asked for with `PLEASE` would be refused.
+    for (_registry, _demanding_registries, _social_credit) in [("FIRST", (2, 1), -76), ("SECOND", (0, 1), -67)] {
+        let mut machine = machine(&source(_registry), Mood::Bored);
+        assert_eq!(machine.run(), Outcome::Halted, "comparing the {} registry", _registry);
+        assert_eq!((machine.refusals, machine.demanding_registries, machine.social_credit), (0, _demanding_registries, _social_credit));
+    }
+}
+
+#[test]
+fn orders_past_the_medium_tolerance_are_a_rage_quit() {
+    let mut machine = machine(&"I'M ORDERING YOU, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(20), Mood::Bored);
+    assert_eq!(machine.run(), Outcome::RageQuit);
+    assert_eq!((machine.pc, machine.social_credit), (15, -75));
+    assert_eq!(machine.sulked, Duration::from_millis(1000));
+    assert_eq!(machine.remark, Some("\"No, you know what, f*** you!\""));
+}
+
+#[test]
+fn registries_wrap_around() {
+    let source = format!("PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n{}", "PLEASE, TO, THE FIRST REGISTRY, DOUBLE THE VALUE.\n".repeat(31));
+    let mut doubled = machine(&source, Mood::Maniacal);
+    assert_eq!(doubled.run(), Outcome::Halted);
+    assert_eq!(doubled.polite_registries.0, i32::MIN);
+
+    // Orders are carried out twice over, past i32::MAX too.
+    let mut ordered = machine(
+        "NOW, INCREMENT, THE FIRST REGISTRY, NEGATIVELY, USING ONE.\n\
+         I'M ORDERING YOU, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n",
+        Mood::Maniacal,
+    );
+    ordered.demanding_registries = (i32::MAX, 0);
+    assert_eq!(ordered.run(), Outcome::Halted);
+    assert_eq!(ordered.demanding_registries.0, i32::MIN);
+}
+
+#[test]
+fn moods_are_seeded_by_the_hour() {
+    let evening = Utc.with_ymd_and_hms(2021, 12, 24, 18, 30, 0).unwrap();
+    assert_eq!(mood::seed_at(evening), 18 * 358);
+    assert_eq!(mood::seed_at(Utc.with_ymd_and_hms(2021, 12, 24, 0, 59, 59).unwrap()), 0);
+    assert_eq!(mood::mood_at(evening), mood::mood_of(18 * 358));
+    assert_eq!(mood::mood_of(1234), mood::mood_of(1234));
+}
+
+#[test]
+fn a_seed_picks_the_mood() {
+    let seeded = Machine::new(&[], Config { mood: Mood::Bored, seed: Some(7), ..Config::default() });
+    assert_eq!(seeded.mood, mood::mood_of(7));
+    let forced = Machine::new(&[], Config { mood: Mood::Sick, ..Config::default() });
+    assert_eq!((forced.mood, forced.social_credit), (Mood::Sick, -25));
+}
+
+#[test]
+fn forecasts_every_hour() {
+    let from = Utc.with_ymd_and_hms(2021, 12, 24, 18, 30, 0).unwrap();
+    let forecast = mood::forecast(from, from + chrono::Duration::hours(3));
+    let hours: Vec<u32> = forecast.iter().map(|(_hour, _)| _hour.hour()).collect();
+    assert_eq!(hours, vec![18, 19, 20, 21]);
+    assert!(forecast.iter().all(|(_hour, _mood)| mood::mood_at(*_hour) == *_mood));
+
+    let lovestruck = mood::next(Mood::Lovestruck, from).unwrap();
+    assert!(lovestruck >= Utc.with_ymd_and_hms(2021, 12, 24, 18, 0, 0).unwrap());
+    assert_eq!(mood::mood_at(lovestruck), Mood::Lovestruck);
+    assert!(mood::forecast(from, lovestruck).iter().all(|(_, _mood)| *_mood != Mood::Lovestruck));
+    assert_eq!("lovestruck".parse::<Mood>(), Ok(Mood::Lovestruck));
+}
+
+#[test]
+fn command_line() {
+    let arguments = |line: &str| line.split_whitespace().map(|_argument| _argument.to_string()).collect::<Vec<String>>();
+    let executable = std::env::temp_dir().join("emulator_command_line.salexe");
+    let program = "PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(30);
+    std::fs::write(&executable, compile_str(&program).unwrap().bytes).unwrap();
+    let executable = executable.to_string_lossy();
+
+    assert_eq!(emulate(arguments("--help")), SUCCESS);
+    assert_eq!(emulate(arguments("mood --version")), SUCCESS);
+    assert_eq!(emulate(arguments("mood --seed 42")), SUCCESS);
+    assert_eq!(emulate(arguments("mood forecast 2021-12-24 2021-12-24T03:00:00Z")), SUCCESS);
+    assert_eq!(emulate(arguments("mood next lovestruck 2021-12-24")), SUCCESS);
+    assert_eq!(emulate(arguments(&format!("--mood maniacal run {}", executable))), SUCCESS);
+    assert_eq!(emulate(arguments(&format!("run {} --mood Bored --no-sulk", executable))), Outcome::Refused.exit_code());
+    assert_eq!(emulate(arguments(&format!("run {} --mood Bored --refusal skip", executable))), SUCCESS);
+    assert_eq!(emulate(arguments("mood --refusal sometimes")), USAGE_ERROR);
+    assert_eq!(emulate(arguments(&format!("run {} --mood Bored --refusal skip --max-steps 10", executable))), Outcome::OutOfSteps.exit_code());
+    assert_eq!(emulate(arguments("mood --time-limit -1")), USAGE_ERROR);
+    assert_eq!(emulate(arguments("debug")), USAGE_ERROR);
+    assert_eq!(emulate(arguments("run no_such_file.salexe --mood Bored")), IO_ERROR);
+
+    assert_eq!(emulate(arguments("")), USAGE_ERROR);
+    assert_eq!(emulate(arguments("get_mood")), USAGE_ERROR);
+    assert_eq!(emulate(arguments(&format!("run {} --Happy", executable))), USAGE_ERROR);
+    assert_eq!(emulate(arguments("run --mood Grumpy x.salexe")), USAGE_ERROR);
+    assert_eq!(emulate(arguments("run a.salexe b.salexe")), USAGE_ERROR);
+    assert_eq!(emulate(arguments("mood --mood happy --seed 1")), USAGE_ERROR);
+    assert_eq!(emulate(arguments("mood --seed 1 --at 2021-12-24T18:00:00Z")), USAGE_ERROR);
+    assert_eq!(emulate(arguments("mood --at 2021-12-24T18:00:00Z --seed 1")), USAGE_ERROR);
+    assert_eq!(emulate(arguments("--seed")), USAGE_ERROR);
+}
+
+#[test]
+fn flip_flopping_gets_the_machine_fed_up() {
+    let mut machine = machine(
+        &"PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+          NOW, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(200),
+        Mood::Annoyed,
+    );
+    machine.social_credit = 0;
+    machine.temperament.polite_social_change = 0;
+    machine.temperament.demanding_social_change = 0;
+    assert_eq!(machine.run(), Outcome::FedUp);
+    assert_eq!((machine.steps, machine.irritation), (126, 1000));
+    let codes: std::collections::HashSet<i32> =
+        [Outcome::Halted, Outcome::Refused, Outcome::FedUp, Outcome::WalkedOut, Outcome::RageQuit].iter().map(Outcome::exit_code).collect();
+    assert_eq!(codes.len(), 5);
+}
+
+#[test]
+fn sulking_takes_virtual_time() {
+    let mut machine = machine(&"I'M BEGGING YOU, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(30), Mood::Bored)
+        .with_clock(Box::new(VirtualClock::default()));
+    let started = std::time::Instant::now();
+    assert_eq!(machine.run(), Outcome::WalkedOut);
+    assert!(started.elapsed() < Duration::from_millis(500));
+    // Begging past -75 sulks for 1.5s each time, until walking out after -100 with 0.5s more.
+    assert_eq!((machine.steps, machine.social_credit), (20, -100));
+    assert_eq!(machine.sulked, Duration::from_millis(5 * 1500 + 500));
+}
+
+#[test]
+fn sulking_counts_towards_the_time_limit() {
+    let begging = "I'M BEGGING YOU, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(30);
+    let program = compile_str(&begging).unwrap().bytes;
+
+    // The first 1.5s sulk is cut short at the limit, whichever clock it is on.
+    let mut virtual_time = Machine::new(&program, Config { mood: Mood::Bored, virtual_time: true, time_limit: Some(Duration::from_secs(1)), ..Config::default() });
+    assert_eq!(virtual_time.run(), Outcome::TimedOut);
+    assert_eq!(virtual_time.steps, 16);
+    assert!(virtual_time.sulked > Duration::from_millis(900) && virtual_time.sulked <= Duration::from_secs(1));
+
+    let mut real_time = Machine::new(&program, Config { mood: Mood::Bored, time_limit: Some(Duration::from_millis(100)), ..Config::default() });
+    let started = std::time::Instant::now();
+    assert_eq!(real_time.run(), Outcome::TimedOut);
+    assert!(started.elapsed() < Duration::from_millis(1000));
+    assert!(real_time.sulked <= Duration::from_millis(100));
+}
+
+#[test]
+fn reads_and_writes_through_io() {
+    let (buffer, output) = io::buffer("41\nx\n");
+    let mut machine = machine(
+        "PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n\
+         PLEASE, ACCESS, THE SECOND REGISTRY, INPUTTING A VALUE, AS A CHARACTER.\n\
+         PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+         PLEASE, ACCESS, THE FIRST REGISTRY, OUTPUTTING A VALUE, AS AN INTEGER.\n\
+         PLEASE, ACCESS, THE SECOND REGISTRY, OUTPUTTING A VALUE, AS A CHARACTER.\n\
+         PLEASE, ACCESS, THE SECOND REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n",
+        Mood::Bored,
+    )
+    .with_io(Box::new(buffer));
+    assert_eq!(machine.run(), Outcome::IoError);
+    assert_eq!((machine.pc, machine.io_error.as_deref()), (5, Some("there is no more input")));
+    assert_eq!(machine.polite_registries, (42, 'x' as i32));
+    assert_eq!(output.text(), "42\nx");
+}
+
+#[test]
+fn reads_and_writes_files() {
+    let input = std::env::temp_dir().join("emulator_files.input");
+    let output = std::env::temp_dir().join("emulator_files.output");
+    std::fs::write(&input, "7\n").unwrap();
+    let mut machine = machine(
+        "PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n\
+         PLEASE, TO, THE FIRST REGISTRY, DOUBLE THE VALUE.\n\
+         PLEASE, ACCESS, THE FIRST REGISTRY, OUTPUTTING A VALUE, AS AN INTEGER.\n",
+        Mood::Bored,
+    )
+    .with_io(Box::new(io::files(&input, &output).unwrap()));
+    assert_eq!(machine.run(), Outcome::Halted);
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), "14\n");
+}
+
+#[test]
+fn reads_signed_integers_and_single_characters() {
+    let program = "PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n\
+                   PLEASE, ACCESS, THE SECOND REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n\
+                   NOW, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS A CHARACTER.\n\
+                   NOW, ACCESS, THE SECOND REGISTRY, INPUTTING A VALUE, AS A CHARACTER.\n\
+                   NOW, ACCESS, THE SECOND REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n";
+    let (buffer, _) = io::buffer("  -5 +12\nHé 3\n");
+    let mut machine = machine(program, Mood::Bored).with_io(Box::new(buffer));
+    assert_eq!(machine.run(), Outcome::Halted);
+    assert_eq!(machine.polite_registries, (-5, 12));
+    assert_eq!(machine.demanding_registries, ('H' as i32, 3));
+
+    // An integer takes the line break after it, but not a blank line.
+    let (buffer, _) = io::buffer("1\n2\n\nx\n7");
+    let mut blank_line = self::machine(program, Mood::Bored).with_io(Box::new(buffer));
+    assert_eq!(blank_line.run(), Outcome::Halted);
+    assert_eq!(blank_line.polite_registries, (1, 2));
+    assert_eq!(blank_line.demanding_registries, ('\n' as i32, 7));
+}
+
+#[test]
+fn malformed_input_is_an_error() {
+    let (buffer, _) = io::buffer("12abc\n");
+    let mut machine = machine("PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n", Mood::Bored)
+        .with_io(Box::new(buffer));
+    assert_eq!(machine.run(), Outcome::IoError);
+    assert_eq!(machine.pc, 0);
+    assert!(machine.io_error.unwrap().contains("`12abc`"));
+}
+
+#[test]
+fn running_out_of_input_follows_the_policy() {
+    let program = &compile_str(
+        "PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n\
+         PLEASE, ACCESS, THE SECOND REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n",
+    )
+    .unwrap()
+    .bytes;
+    let run = |eof: EofPolicy| {
+        let (buffer, _) = io::buffer("4");
+        let mut machine = Machine::new(program, Config { eof, virtual_time: true, ..Config::default() }).with_io(Box::new(buffer));
+        (machine.run(), machine.pc, machine.polite_registries)
+    };
+    assert_eq!(run(EofPolicy::Sentinel(-1)), (Outcome::Halted, 2, (4, -1)));
+    assert_eq!(run(EofPolicy::Refuse), (Outcome::Refused, 1, (4, 0)));
+    assert_eq!(run(EofPolicy::Error), (Outcome::IoError, 1, (4, 0)));
+    assert_eq!("-1".parse::<EofPolicy>(), Ok(EofPolicy::Sentinel(-1)));
+}
+
+#[test]
+fn failed_instructions_are_charged_for_once_carried_out() {
+    // Input that runs out once, and then has a value after all.
+    #[derive(Debug)]
+    struct Late(Vec<Option<i32>>);
+    impl io::Io for Late {
+        fn read_integer(&mut self) -> std::io::Result<Option<i32>> {
+            Ok(self.0.pop().flatten())
+        }
+        fn read_character(&mut self) -> std::io::Result<Option<char>> {
+            Ok(None)
+        }
+        fn write_integer(&mut self, _: i32) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn write_character(&mut self, _: char) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let program = compile_str(
+        "NOW, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n\
+         PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n",
+    )
+    .unwrap()
+    .bytes;
+    let config = Config { mood: Mood::Happy, eof: EofPolicy::Refuse, virtual_time: true, ..Config::default() };
+    let mut machine = Machine::new(&program, config).with_io(Box::new(Late(vec![Some(4), None])));
+    assert_eq!(machine.step(), None);
+    let (social_credit, irritation) = (machine.social_credit, machine.irritation);
+    assert!(irritation > 0);
+
+    assert_eq!(machine.step(), Some(Outcome::Refused));
+    assert_eq!((machine.pc, machine.social_credit, machine.irritation, machine.last_was_positive), (1, social_credit, irritation, false));
+    assert_eq!(machine.step(), None);
+    assert_eq!((machine.pc, machine.social_credit - social_credit, machine.polite_registries.0), (2, 2, 4));
+    assert_eq!((machine.irritation, machine.last_was_positive), (2 * irritation, true));
+}
+
+#[test]
+fn writes_characters_as_text() {
+    let program = &compile_str(
+        &"PLEASE, ACCESS, THE FIRST REGISTRY, INPUTTING A VALUE, AS AN INTEGER.\n\
+          PLEASE, ACCESS, THE FIRST REGISTRY, OUTPUTTING A VALUE, AS A CHARACTER.\n"
+            .repeat(4),
+    )
+    .unwrap()
+    .bytes;
+    let run = |input: &str, invalid_char: CharPolicy| {
+        let (buffer, output) = io::buffer(input);
+        let config = Config { mood: Mood::Maniacal, invalid_char, ..Config::default() };
+        let mut machine = Machine::new(program, config).with_io(Box::new(buffer));
+        (machine.run(), output.text())
+    };
+    assert_eq!(run("72 105 10 33", CharPolicy::Replace), (Outcome::Halted, "Hi\n!".to_string()));
+    assert_eq!(run("233 8364 128578 32", CharPolicy::Replace).1, "é€🙂 ");
+    assert_eq!(run("79 55296 75 -1", CharPolicy::Replace).1, "O\u{FFFD}K\u{FFFD}");
+    assert_eq!(run("79 1114112 75 -1", CharPolicy::Skip).1, "OK");
+    assert_eq!(run("79 -2 75 75", CharPolicy::Error), (Outcome::IoError, "O".to_string()));
+}
+
+#[test]
+fn refusals_follow_the_policy() {
+    let program = compile_str(&"PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(30)).unwrap().bytes;
+    let machine = |refusal: RefusalPolicy| Machine::new(&program, Config { mood: Mood::Bored, virtual_time: true, refusal, ..Config::default() });
+
+    let mut skipping = machine(RefusalPolicy::Skip);
+    assert_eq!(skipping.run(), Outcome::Halted);
+    assert_eq!((skipping.pc, skipping.steps, skipping.refusals, skipping.polite_registries.0), (30, 25, 5, 25));
+
+    let mut retrying = machine("retry:2:100".parse().unwrap());
+    assert_eq!(retrying.run(), Outcome::Refused);
+    assert_eq!((retrying.pc, retrying.steps, retrying.refusals), (25, 25, 3));
+    assert_eq!(retrying.sulked, Duration::from_millis(100 + 200));
+
+    let mut aborting = machine(RefusalPolicy::default());
+    assert_eq!(aborting.run(), Outcome::Refused);
+    assert_eq!(aborting.refusals, 1);
+
+    assert_eq!("retry".parse(), Ok(RefusalPolicy::Retry { attempts: 3, backoff: Duration::from_millis(500) }));
+    assert!("retry:many".parse::<RefusalPolicy>().is_err());
+    assert!("skip:1".parse::<RefusalPolicy>().is_err());
+}
+
+#[test]
+fn jumps_out_of_the_program_are_a_fault() {
+    let increment = Operation::Increment { registry: Registry::First, negatively: false, using_other_registry: false };
+
+    let mut backwards = Machine::new(&encode(&[(Prefix::Now, Operation::Jump { distance: 7 })]), Config::default());
+    assert_eq!(
+        backwards.run(),
+        Outcome::JumpOutOfBounds { address: 0, offset: 8, prefix: Prefix::Now, doubled: false }
+    );
+    assert_eq!((backwards.pc, backwards.steps), (0, 0));
+
+    // Landing right after the last instruction halts, one further is a fault.
+    let forwards = |distance: u8| encode(&[(Prefix::Please, Operation::Jump { distance }), (Prefix::Please, increment), (Prefix::Please, increment)]);
+    assert_eq!(Machine::new(&forwards(1), Config::default()).run(), Outcome::Halted);
+    let mut past_the_end = Machine::new(&forwards(2), Config::default());
+    assert_eq!(past_the_end.run().exit_code(), 15);
+
+    // Maniacal carries out every order twice over, and twice as far is too far.
+    let ordered = encode(&[
+        (Prefix::Please, increment),
+        (Prefix::Please, increment),
+        (Prefix::Ordering, Operation::Jump { distance: 1 }),
+    ]);
+    let mut doubled = Machine::new(&ordered, Config { mood: Mood::Maniacal, virtual_time: true, ..Config::default() });
+    assert_eq!(
+        doubled.run(),
+        Outcome::JumpOutOfBounds { address: 2, offset: 4, prefix: Prefix::Ordering, doubled: true }
+    );
+}
+
+#[test]
+fn branches_if_zero_move_like_every_other_branch() {
+    // Before sal-isa, a taken BranchIfZero moved one address less than the other branches.
+    let increment = Operation::Increment { registry: Registry::First, negatively: false, using_other_registry: false };
+    let landing = |prefix: Prefix, at: usize, operation: Operation| {
+        let mut program = vec![(Prefix::Please, increment); 4];
+        program[at] = (prefix, operation);
+        let mut machine = Machine::new(&encode(&program), Config { mood: Mood::Bored, virtual_time: true, ..Config::default() });
+        machine.pc = at;
+        assert_eq!(machine.step(), None);
+        machine.pc
+    };
+    for (_prefix, _at, _distance, _target) in [(Prefix::Please, 0, 0, 2), (Prefix::Please, 0, 1, 3), (Prefix::Now, 3, 1, 2), (Prefix::Now, 3, 2, 1)] {
+        let zero = landing(_prefix, _at, Operation::BranchIfZero { registry: Registry::Second, distance: _distance });
+        let equal = landing(_prefix, _at, Operation::JumpIfEqual { distance: _distance });
+        assert_eq!((zero, equal), (_target, _target), "{:?} from {} by {}", _prefix, _at, _distance);
+    }
+}
+
+#[test]
+fn disassembled_branches_land_where_the_machine_jumps() {
+    use the_socially_acceptable_language_compiler::disassembler::disassemble;
+
+    let increment = |registry: Registry| Operation::Increment { registry, negatively: false, using_other_registry: false };
+    let executable = encode(&[
+        (Prefix::Please, Operation::BranchIfZero { registry: Registry::First, distance: 1 }),
+        (Prefix::Please, increment(Registry::First)),
+        (Prefix::Please, increment(Registry::First)),
+        (Prefix::Please, increment(Registry::Second)),
+        (Prefix::Please, increment(Registry::Second)),
+    ]);
+    let mut machine = Machine::new(&executable, Config { mood: Mood::Bored, virtual_time: true, ..Config::default() });
+    assert_eq!(machine.run(), Outcome::Halted);
+    assert_eq!(machine.polite_registries, (0, 2));
+
+    // The label goes on the instruction the machine jumped to, and the source runs the same.
+    let disassembly = disassemble(&executable);
+    assert!(disassembly.contains("THE L3:\nPLEASE, INCREMENT, THE SECOND REGISTRY"), "{}", disassembly);
+    let mut disassembled = self::machine(&disassembly, Mood::Bored);
+    assert_eq!(disassembled.run(), Outcome::Halted);
+    assert_eq!(disassembled.polite_registries, machine.polite_registries);
+}
+
+#[test]
+fn runaway_programs_are_stopped() {
+    // A machine that doesn't mind how it's asked, so only the program decides what it does.
+    let machine = |executable: &[u8], config: Config| {
+        let mut machine = Machine::new(executable, Config { virtual_time: true, ..config });
+        machine.temperament.polite_social_change = 0;
+        machine.temperament.demanding_social_change = 0;
+        machine.temperament.irritation_change = 0;
+        machine
+    };
+    let increment = Operation::Increment { registry: Registry::First, negatively: false, using_other_registry: false };
+
+    // Counts up forever, never in the same state twice.
+    let counting = encode(&[(Prefix::Please, increment), (Prefix::Now, Operation::Jump { distance: 1 })]);
+    let mut budgeted = machine(&counting, Config { max_steps: Some(100), detect_loops: true, ..Config::default() });
+    assert_eq!(budgeted.run(), Outcome::OutOfSteps);
+    assert_eq!((budgeted.steps, budgeted.polite_registries.0), (100, 50));
+    let mut timed = machine(&counting, Config { time_limit: Some(Duration::from_millis(10)), ..Config::default() });
+    assert_eq!(timed.run(), Outcome::TimedOut);
+
+    // Jumps to itself forever.
+    let spinning = encode(&[(Prefix::Please, increment), (Prefix::Now, Operation::Jump { distance: 0 })]);
+    let mut looping = machine(&spinning, Config { detect_loops: true, ..Config::default() });
+    assert_eq!(looping.run(), Outcome::Looping);
+    assert_eq!((looping.pc, looping.polite_registries.0), (1, 1));
+    assert!(looping.steps < 10);
+
+    // Reading input each time round isn't a loop, even if the same value is read every time.
+    let reading = encode(&[
+        (Prefix::Please, Operation::Access { registry: Registry::First, outputting: false, as_character: false }),
+        (Prefix::Now, Operation::Jump { distance: 1 }),
+    ]);
+    let mut reader = machine(&reading, Config { detect_loops: true, ..Config::default() }).with_io(Box::new(io::buffer(&"7 ".repeat(20)).0));
+    assert_eq!(reader.run(), Outcome::IoError);
+    assert_eq!(reader.steps, 40);
+}
+
+#[test]
+fn debugger_stops_at_breakpoints_and_watchpoints() {
+    let increment = "PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n";
+    let program = compile_str(&format!("{}\n{}", increment, increment.repeat(29))).unwrap();
+    let mut debugger = Debugger::new(machine("", Mood::Bored), None);
+    assert!(debugger.break_at_line(2).is_err());
+
+    debugger = Debugger::new(
+        Machine::new(&program.bytes, Config { mood: Mood::Bored, virtual_time: true, ..Config::default() }),
+        Some(program.lines),
+    );
+    assert_eq!(debugger.break_at_line(2), Ok(1));
+    assert_eq!(debugger.resume(), Stop::Breakpoint(1));
+    assert_eq!(debugger.line(1), Some(3));
+    debugger.watch(Tolerance::SmallClose);
+    assert_eq!(debugger.resume(), Stop::Watchpoint { tolerance: Tolerance::SmallClose, from: 24, to: 26 });
+    assert_eq!(debugger.machine.steps, 13);
+    assert_eq!(debugger.step(), Stop::Stepped);
+
+    let mut output = Vec::new();
+    debugger.session("print\nbreak 20\nbreak\nwatch loud\nc\nd 20\nc\nstep\nquit\nprint\n".as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("polite registries: 14 0\n"), "{}", output);
+    assert!(output.contains("social credit 28, irritation 0, mood Bored\n"));
+    assert!(output.contains("breakpoints: 1, 20\nwatching: small-close\n"));
+    assert!(output.contains("error: expected small, medium or large"));
+    assert!(output.contains("Breakpoint at address 20 (line 22)"));
+    assert!(output.contains("Refused at address 25: the program asked politely once too often."));
+    assert_eq!(output.matches("polite registries").count(), 1);
+    assert_eq!(debugger.outcome, Some(Outcome::Refused));
+    assert_eq!(debugger.step(), Stop::Ended(Outcome::Refused));
+}
+
+#[test]
+fn traces_every_instruction() {
+    let trace = io::SharedBuffer::default();
+    let config = Config { mood: Mood::Bored, virtual_time: true, refusal: RefusalPolicy::Retry { attempts: 1, backoff: Duration::from_millis(100) }, ..Config::default() };
+    let program = compile_str(
+        &("PLEASE, TO, THE FIRST REGISTRY, PUSH THE VALUE.\n".to_string()
+            + &"PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(25)),
+    )
+    .unwrap();
+    let mut traced = Machine::new(&program.bytes, config).with_trace(Trace::new(Box::new(trace.clone())));
+    assert_eq!(traced.run(), Outcome::Refused);
+    let text = trace.text();
+    let lines: Vec<&str> = text.lines().collect();
+    // 25 instructions run, the last one refused, retried once and refused again.
+    assert_eq!(lines.len(), 27);
+    assert!(lines.iter().all(|_line| _line.starts_with('{') && _line.ends_with('}')));
+    assert!(lines[0].starts_with(
+        "{\"step\":0,\"address\":0,\"prefix\":\"Please\",\"operation\":\"To\",\"specifics\":{\"registry\":\"First\",\"action\":\"Push\"},\
+         \"before\":{\"polite_registries\":[0,0],\"demanding_registries\":[0,0],\"social_credit\":0,\"irritation\":0},\
+         \"after\":{\"polite_registries\":[0,0],\"demanding_registries\":[0,0],\"social_credit\":2,\"irritation\":0},\
+         \"stack_depths\":[1,0],"
+    ));
+    assert!(lines[1].contains("\"before\":{\"polite_registries\":[0,0]") && lines[1].contains("\"after\":{\"polite_registries\":[1,0]"));
+    assert!(lines[24].ends_with("\"refused\":false,\"doubled\":false,\"slept_ms\":0,\"remark\":\"\\\"I guess...\\\"\",\"outcome\":null}"));
+    assert!(lines[25].starts_with("{\"step\":25,\"address\":25,"));
+    assert!(lines[25].ends_with("\"refused\":true,\"doubled\":false,\"slept_ms\":100,\"remark\":null,\"outcome\":null}"));
+    assert!(lines[26].ends_with("\"refused\":true,\"doubled\":false,\"slept_ms\":0,\"remark\":null,\"outcome\":\"Refused\"}"));
+
+    // Maniacal carries out every order twice over.
+    let trace = io::SharedBuffer::default();
+    let mut ordered = machine("I'M ORDERING YOU, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n", Mood::Maniacal)
+        .with_trace(Trace::new(Box::new(trace.clone())));
+    assert_eq!(ordered.run(), Outcome::Halted);
+    assert!(trace.text().contains("\"after\":{\"polite_registries\":[0,0],\"demanding_registries\":[2,0],"));
+    assert!(trace.text().contains("\"doubled\":true"));
+
+    // But only increments and jumps taken are doubled.
+    let trace = io::SharedBuffer::default();
+    let mut ordered = machine(
+        "THE START:\n\
+         I'M ORDERING YOU, TO, THE FIRST REGISTRY, PUSH THE VALUE.\n\
+         I'M ORDERING YOU, INCREMENT, THE SECOND REGISTRY, POSITIVELY, USING ONE.\n\
+         I'M ORDERING YOU, IF THE SPECIFIED REGISTRY IS EQUAL TO ZERO JUMP TO THE SPECIFIED LABEL, COMPARING THE SECOND REGISTRY, AND JUMPING TO THE START.\n",
+        Mood::Maniacal,
+    )
+    .with_trace(Trace::new(Box::new(trace.clone())));
+    assert_eq!(ordered.run(), Outcome::Halted);
+    let doubled: Vec<bool> = trace.text().lines().map(|_line| _line.contains("\"doubled\":true")).collect();
+    assert_eq!(doubled, [false, true, false]);
+}
+
+#[test]
+fn exports_the_social_timeline() {
+    let mut machine = machine(&"I'M ORDERING YOU, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n".repeat(20), Mood::Bored).with_timeline();
+    assert_eq!(machine.run(), Outcome::RageQuit);
+    let timeline = machine.timeline().unwrap();
+    // Before the first instruction, after each of the 15 run, and after the one ordered past the medium tolerance.
+    assert_eq!(timeline.points.len(), 17);
+    assert_eq!(timeline.points[0], Point { steps: 0, pc: 0, social_credit: 0, irritation: 0, last_was_positive: true });
+    assert!(!timeline.points[1].last_was_positive);
+    assert_eq!((timeline.points[16].steps, timeline.points[16].social_credit), (15, -75));
+
+    let csv = timeline.csv();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(rows.len(), 18);
+    assert_eq!(
+        rows[0],
+        "steps,pc,social_credit,irritation,last_was_positive,\
+         small_close_tolerance,small_tolerance,medium_close_tolerance,medium_tolerance,large_close_tolerance,large_tolerance"
+    );
+    assert_eq!(rows[1], "0,0,0,0,true,25,50,50,75,75,100");
+    assert_eq!(rows[2], "1,1,-5,4,false,25,50,50,75,75,100");
+
+    let svg = timeline.svg();
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\"") && svg.ends_with("</svg>\n"));
+    assert!(!svg.contains("href"));
+    assert_eq!(svg.matches("<polyline").count(), 2);
+    assert!(svg.contains(">small, medium-close -50</text>") && svg.contains(">large 100</text>"));
+    assert!(svg.contains("a Bored machine, over 15 instruction(s)"));
+    assert_eq!((svg.matches("fill=\"#3a9d3a\"").count(), svg.matches("fill=\"#c83232\"").count()), (1, 1));
+
+    let executable = std::env::temp_dir().join("emulator_timeline.salexe");
+    std::fs::write(&executable, compile_str("PLEASE, INCREMENT, THE FIRST REGISTRY, POSITIVELY, USING ONE.\n").unwrap().bytes).unwrap();
+    let chart = std::env::temp_dir().join("emulator_timeline.svg");
+    let arguments = ["run", &executable.to_string_lossy(), "--mood", "bored", "--timeline-svg", &chart.to_string_lossy()].map(|_argument| _argument.to_string());
+    assert_eq!(emulate(arguments.to_vec()), SUCCESS);
+    assert!(std::fs::read_to_string(chart).unwrap().contains("a Bored machine, over 1 instruction(s)"));
+}
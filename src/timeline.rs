@@ -0,0 +1,175 @@
+/***
+ * Emulator for The Socially Acceptable Language
+ * - how the machine felt about a program, instruction by instruction
+ */
+use std::fmt::Write;
+
+use crate::machine::{Machine, Mood, Temperament, Tolerance, IRRITATION_LIMIT};
+
+// Size of the chart, and where its three parts go.
+const WIDTH: f64 = 900.0;
+const LEFT: f64 = 150.0;
+const RIGHT: f64 = 880.0;
+const CREDIT_TOP: f64 = 40.0;
+const CREDIT_BOTTOM: f64 = 300.0;
+const IRRITATION_TOP: f64 = 330.0;
+const IRRITATION_BOTTOM: f64 = 430.0;
+const POSITIVE_TOP: f64 = 450.0;
+const POSITIVE_BOTTOM: f64 = 465.0;
+const HEIGHT: f64 = 490.0;
+
+/// The social state of the machine at one point of a run.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Point {
+    /// Instructions run so far.
+    pub steps: u64,
+    /// Address of the next instruction.
+    pub pc: usize,
+    pub social_credit: i32,
+    pub irritation: i32,
+    pub last_was_positive: bool,
+}
+
+impl Point {
+    pub fn of(machine: &Machine) -> Point {
+        Point {
+            steps: machine.steps,
+            pc: machine.pc,
+            social_credit: machine.social_credit,
+            irritation: machine.irritation,
+            last_was_positive: machine.last_was_positive,
+        }
+    }
+}
+
+/// ## Timeline
+/// The social state before a run, and after every instruction of it, refused ones included,
+/// kept by `Machine::with_timeline`. Exported as CSV, or drawn as an SVG chart against the
+/// tolerances of the machine's mood.
+///
+/// Every point is kept until the timeline is dropped, one `Point` per instruction run, so a
+/// long run needs a step budget, see `Config::max_steps`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Timeline {
+    pub mood: Mood,
+    pub temperament: Temperament,
+    pub points: Vec<Point>,
+}
+
+impl Timeline {
+    /// A timeline starting where `machine` is.
+    pub fn new(machine: &Machine) -> Timeline {
+        Timeline { mood: machine.mood, temperament: machine.temperament, points: vec![Point::of(machine)] }
+    }
+
+    /// One row per point, with the tolerances repeated on every row so the file can be
+    /// charted on its own.
+    pub fn csv(&self) -> String {
+        let mut csv = "steps,pc,social_credit,irritation,last_was_positive".to_string();
+        for _tolerance in Tolerance::ALL {
+            write!(csv, ",{}_tolerance", _tolerance.to_string().replace('-', "_")).unwrap();
+        }
+        csv.push('\n');
+        for _point in &self.points {
+            write!(csv, "{},{},{},{},{}", _point.steps, _point.pc, _point.social_credit, _point.irritation, _point.last_was_positive).unwrap();
+            for _tolerance in Tolerance::ALL {
+                write!(csv, ",{}", _tolerance.of(&self.temperament)).unwrap();
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// A chart of the timeline, needing nothing but itself.
+    ///
+    /// Social credit is drawn on top of the bands between each tolerance and how close
+    /// to it counts as close, on both sides of zero. Irritation is drawn below it, up to
+    /// `IRRITATION_LIMIT`, and below that whether the last instruction was polite, in green,
+    /// or demanding, in red.
+    pub fn svg(&self) -> String {
+        let temperament = &self.temperament;
+        // Room for every point and every tolerance, on both sides of zero.
+        let largest = self
+            .points
+            .iter()
+            .map(|_point| _point.social_credit.abs())
+            .chain(Tolerance::ALL.iter().map(|_tolerance| _tolerance.of(temperament).abs()))
+            .max()
+            .unwrap_or(0);
+        let reach = (largest + largest / 10).max(1) as f64;
+        let x = |_index: usize| LEFT + (RIGHT - LEFT) * _index as f64 / (self.points.len().max(2) - 1) as f64;
+        let credit_y = |_credit: i32| (CREDIT_TOP + CREDIT_BOTTOM) / 2.0 - (CREDIT_BOTTOM - CREDIT_TOP) / 2.0 * _credit as f64 / reach;
+        let irritation_y = |_irritation: i32| {
+            IRRITATION_BOTTOM - (IRRITATION_BOTTOM - IRRITATION_TOP) * _irritation.clamp(0, IRRITATION_LIMIT) as f64 / IRRITATION_LIMIT as f64
+        };
+
+        // Instructions refused, or never run because the run ended at them, still have a point of their own.
+        let steps = self.points.last().map_or(0, |_point| _point.steps);
+
+        let mut svg = String::new();
+        writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="sans-serif" font-size="11">"#, WIDTH, HEIGHT, WIDTH, HEIGHT).unwrap();
+        writeln!(svg, r#"<rect width="{}" height="{}" fill="white"/>"#, WIDTH, HEIGHT).unwrap();
+        writeln!(svg, r#"<text x="{}" y="20" font-size="14">Social credit and irritation of a {:?} machine, over {} instruction(s)</text>"#, LEFT, self.mood, steps).unwrap();
+
+        // Every band runs from close to a tolerance to past it, above zero and below it.
+        let bands = [
+            (Tolerance::SmallClose, Tolerance::Small, "#fff3b0"),
+            (Tolerance::MediumClose, Tolerance::Medium, "#ffd1a3"),
+            (Tolerance::LargeClose, Tolerance::Large, "#ffb3b3"),
+        ];
+        for (_close, _tolerance, _colour) in bands {
+            let (close, tolerance) = (_close.of(temperament), _tolerance.of(temperament));
+            for _sign in [1, -1] {
+                let (top, bottom) = (credit_y(_sign * close.max(tolerance)), credit_y(_sign * close.min(tolerance)));
+                let (top, bottom) = (top.min(bottom).max(CREDIT_TOP), top.max(bottom).min(CREDIT_BOTTOM));
+                if bottom > top {
+                    writeln!(svg, r#"<rect x="{}" y="{:.1}" width="{}" height="{:.1}" fill="{}"/>"#, LEFT, top, RIGHT - LEFT, bottom - top, _colour).unwrap();
+                }
+            }
+        }
+        // Moods often have one tolerance close to the next, so those share a line.
+        let mut lines: Vec<(i32, Vec<String>, bool)> = Vec::new();
+        for _tolerance in Tolerance::ALL {
+            let (value, close) = (_tolerance.of(temperament), [Tolerance::SmallClose, Tolerance::MediumClose, Tolerance::LargeClose].contains(&_tolerance));
+            match lines.iter_mut().find(|(_value, _, _)| *_value == value) {
+                Some((_, _names, _close)) => {
+                    _names.push(_tolerance.to_string());
+                    *_close &= close;
+                }
+                None => lines.push((value, vec![_tolerance.to_string()], close)),
+            }
+        }
+        for (_value, _names, _close) in &lines {
+            let dashes = if *_close { "2,3" } else { "6,3" };
+            for _value in [*_value, -*_value] {
+                let y = credit_y(_value);
+                writeln!(svg, r##"<line x1="{}" y1="{:.1}" x2="{}" y2="{:.1}" stroke="#888" stroke-dasharray="{}"/>"##, LEFT, y, RIGHT, y, dashes).unwrap();
+                writeln!(svg, r##"<text x="{}" y="{:.1}" text-anchor="end" fill="#555">{} {}</text>"##, LEFT - 4.0, y + 4.0, _names.join(", "), _value).unwrap();
+            }
+        }
+        writeln!(svg, r#"<line x1="{}" y1="{:.1}" x2="{}" y2="{:.1}" stroke="black"/>"#, LEFT, credit_y(0), RIGHT, credit_y(0)).unwrap();
+
+        let credit: Vec<String> = self.points.iter().enumerate().map(|(_index, _point)| format!("{:.1},{:.1}", x(_index), credit_y(_point.social_credit))).collect();
+        writeln!(svg, r##"<polyline points="{}" fill="none" stroke="#1f5fbf" stroke-width="2"/>"##, credit.join(" ")).unwrap();
+        writeln!(svg, r##"<text x="{}" y="{}" fill="#1f5fbf">social credit</text>"##, LEFT, CREDIT_TOP - 6.0).unwrap();
+
+        writeln!(svg, r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#ccc"/>"##, LEFT, IRRITATION_TOP, RIGHT - LEFT, IRRITATION_BOTTOM - IRRITATION_TOP).unwrap();
+        let irritation: Vec<String> = self.points.iter().enumerate().map(|(_index, _point)| format!("{:.1},{:.1}", x(_index), irritation_y(_point.irritation))).collect();
+        writeln!(svg, r##"<polyline points="{}" fill="none" stroke="#8e3fbf" stroke-width="2"/>"##, irritation.join(" ")).unwrap();
+        writeln!(svg, r##"<text x="{}" y="{}" fill="#8e3fbf">irritation, up to {}</text>"##, LEFT, IRRITATION_TOP - 6.0, IRRITATION_LIMIT).unwrap();
+
+        // One rectangle for every run of points that are all polite or all demanding.
+        let width = (RIGHT - LEFT) / self.points.len() as f64;
+        let mut first = 0;
+        for _run in self.points.chunk_by(|_previous, _point| _previous.last_was_positive == _point.last_was_positive) {
+            let last = first + _run.len() - 1;
+            let colour = if _run[0].last_was_positive { "#3a9d3a" } else { "#c83232" };
+            let (left, right) = (x(first) - width / 2.0, x(last) + width / 2.0);
+            writeln!(svg, r#"<rect x="{:.1}" y="{}" width="{:.1}" height="{}" fill="{}"/>"#, left, POSITIVE_TOP, right - left, POSITIVE_BOTTOM - POSITIVE_TOP, colour).unwrap();
+            first = last + 1;
+        }
+        writeln!(svg, r##"<text x="{}" y="{}" fill="#555">last instruction polite (green) or demanding (red)</text>"##, LEFT, POSITIVE_BOTTOM + 15.0).unwrap();
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
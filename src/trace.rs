@@ -0,0 +1,167 @@
+/***
+ * Emulator for The Socially Acceptable Language
+ * - a record of every instruction the machine runs, as JSON Lines
+ */
+use std::fmt::Debug;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use sal_isa::{Instruction, Operation};
+
+use crate::machine::{Machine, Outcome};
+
+/// Where `Machine::with_trace` writes one JSON object per instruction, on a line of its own:
+///
+/// ```json
+/// {"step":0,"address":0,"prefix":"Please","operation":"Increment",
+///  "specifics":{"registry":"First","negatively":false,"using_other_registry":false},
+///  "before":{"polite_registries":[0,0],"demanding_registries":[0,0],"social_credit":0,"irritation":0},
+///  "after":{"polite_registries":[1,0],"demanding_registries":[0,0],"social_credit":2,"irritation":0},
+///  "stack_depths":[0,0],"loops":[{"start":0,"counter":0,"times":0},{"start":0,"counter":0,"times":0}],
+///  "refused":false,"doubled":false,"slept_ms":0,"remark":null,"outcome":null}
+/// ```
+///
+/// Stack depths and loops are after the instruction. `step` is how many instructions had been
+/// run before it, so a refused instruction has the same one as the instruction after it.
+/// `remark` is what the machine had to say about the instruction, if anything, see `Machine::remark`.
+/// `outcome` is how the run ended, if the instruction ended it, see `Outcome`'s `Display`.
+/// An instruction the machine got fed up at was never run, but it is still traced.
+pub struct Trace {
+    output: Box<dyn Write>,
+}
+
+// What an instruction can change, taken before it is run.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Before {
+    pub(crate) pc: usize,
+    steps: u64,
+    refusals: u64,
+    polite_registries: (i32, i32),
+    demanding_registries: (i32, i32),
+    pub(crate) social_credit: i32,
+    irritation: i32,
+    sulked: Duration,
+}
+
+impl Before {
+    pub(crate) fn of(machine: &Machine) -> Before {
+        Before {
+            pc: machine.pc,
+            steps: machine.steps,
+            refusals: machine.refusals,
+            polite_registries: machine.polite_registries,
+            demanding_registries: machine.demanding_registries,
+            social_credit: machine.social_credit,
+            irritation: machine.irritation,
+            sulked: machine.sulked,
+        }
+    }
+}
+
+impl Trace {
+    pub fn new(output: Box<dyn Write>) -> Trace {
+        Trace { output }
+    }
+
+    // Write the line for the instruction run from `before` to where `machine` is now.
+    pub(crate) fn record(&mut self, before: &Before, machine: &Machine, instruction: Instruction, doubled: bool, outcome: Option<Outcome>) -> io::Result<()> {
+        let bank = |polite: (i32, i32), demanding: (i32, i32), credit: i32, irritation: i32| {
+            format!(
+                "{{\"polite_registries\":[{},{}],\"demanding_registries\":[{},{}],\"social_credit\":{},\"irritation\":{}}}",
+                polite.0, polite.1, demanding.0, demanding.1, credit, irritation
+            )
+        };
+        let loop_state = |start: usize, counter: i32, times: i32| format!("{{\"start\":{},\"counter\":{},\"times\":{}}}", start, counter, times);
+        writeln!(
+            self.output,
+            "{{\"step\":{},\"address\":{},\"prefix\":{},\"operation\":\"{}\",\"specifics\":{},\
+             \"before\":{},\"after\":{},\"stack_depths\":[{},{}],\"loops\":[{},{}],\
+             \"refused\":{},\"doubled\":{},\"slept_ms\":{},\"remark\":{},\"outcome\":{}}}",
+            before.steps,
+            before.pc,
+            debug_string(instruction.prefix),
+            name(&instruction.operation),
+            specifics(&instruction.operation),
+            bank(before.polite_registries, before.demanding_registries, before.social_credit, before.irritation),
+            bank(machine.polite_registries, machine.demanding_registries, machine.social_credit, machine.irritation),
+            machine.stacks.0.len(),
+            machine.stacks.1.len(),
+            loop_state(machine.loops.0, machine.loop_counters.0, machine.loop_registries.0),
+            loop_state(machine.loops.1, machine.loop_counters.1, machine.loop_registries.1),
+            machine.refusals > before.refusals,
+            doubled,
+            (machine.sulked - before.sulked).as_millis(),
+            machine.remark.map(json_string).unwrap_or_else(|| "null".to_string()),
+            outcome.map(|_outcome| json_string(&_outcome.to_string())).unwrap_or_else(|| "null".to_string()),
+        )
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+impl Debug for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Trace").finish_non_exhaustive()
+    }
+}
+
+fn name(operation: &Operation) -> &'static str {
+    match operation {
+        Operation::Increment { .. } => "Increment",
+        Operation::To { .. } => "To",
+        Operation::Access { .. } => "Access",
+        Operation::Repeat { .. } => "Repeat",
+        Operation::BranchIfGreater { .. } => "BranchIfGreater",
+        Operation::BranchIfZero { .. } => "BranchIfZero",
+        Operation::JumpIfEqual { .. } => "JumpIfEqual",
+        Operation::Jump { .. } => "Jump",
+    }
+}
+
+// The fields of the operation, as a JSON object.
+fn specifics(operation: &Operation) -> String {
+    match *operation {
+        Operation::Increment { registry, negatively, using_other_registry } => format!(
+            "{{\"registry\":{},\"negatively\":{},\"using_other_registry\":{}}}",
+            debug_string(registry), negatively, using_other_registry
+        ),
+        Operation::To { registry, action } => format!("{{\"registry\":{},\"action\":{}}}", debug_string(registry), debug_string(action)),
+        Operation::Access { registry, outputting, as_character } => format!(
+            "{{\"registry\":{},\"outputting\":{},\"as_character\":{}}}",
+            debug_string(registry), outputting, as_character
+        ),
+        Operation::Repeat { registry, ending, second_loop } => {
+            format!("{{\"registry\":{},\"ending\":{},\"second_loop\":{}}}", debug_string(registry), ending, second_loop)
+        }
+        Operation::BranchIfGreater { registry, distance } | Operation::BranchIfZero { registry, distance } => {
+            format!("{{\"registry\":{},\"distance\":{}}}", debug_string(registry), distance)
+        }
+        Operation::JumpIfEqual { distance } | Operation::Jump { distance } => format!("{{\"distance\":{}}}", distance),
+    }
+}
+
+// A value's `Debug`, as a JSON string.
+fn debug_string(value: impl Debug) -> String {
+    json_string(&format!("{:?}", value))
+}
+
+// Quote text as a JSON string, escaping what JSON doesn't allow in one as it is.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for _character in text.chars() {
+        match _character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _control if (_control as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", _control as u32)),
+            _character => quoted.push(_character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}